@@ -0,0 +1,165 @@
+use std::sync::Arc;
+use std::time::Duration;
+use ::error::Error;
+use ::origin::Origin;
+use ::render::render_path;
+use ::value::Value;
+
+/// Resolved configuration, or the part of one found at a path, like the
+/// `db.primary` section a library is given. Paths are relative to that part,
+/// but errors name the full path from the root. Views share the root they
+/// were taken from, so they are cheap to make and pass around.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    root: Arc<Value>,
+    /// Path of this part from the root, empty for the root itself.
+    prefix: String
+}
+
+macro_rules! delegate {
+    () => {};
+    ($(#[$attr:meta])* fn $method:ident(&self, path: &str $(, $arg:ident: $ty:ty)*) -> Result<$ok:ty, Error>; $($rest:tt)*) => {
+        $(#[$attr])*
+        pub fn $method(&self, path: &str $(, $arg: $ty)*) -> Result<$ok, Error> {
+            let path = self.full_path(path);
+            self.root.$method(&path $(, $arg)*).map_err(|err| at_path(&path, err))
+        }
+        delegate! { $($rest)* }
+    };
+    ($(#[$attr:meta])* fn $method:ident(&self, path: &str $(, $arg:ident: $ty:ty)*) -> $ret:ty; $($rest:tt)*) => {
+        $(#[$attr])*
+        pub fn $method(&self, path: &str $(, $arg: $ty)*) -> $ret {
+            self.root.$method(&self.full_path(path) $(, $arg)*)
+        }
+        delegate! { $($rest)* }
+    };
+}
+
+impl Config {
+
+    /// Wraps a resolved value, which must be an object.
+    pub fn new(root: Value) -> Result<Config, Error> {
+        match root {
+            Value::Object(_) => Ok(Config { root: Arc::new(root), prefix: String::new() }),
+            _ => Err(Error::IncompatibleType)
+        }
+    }
+
+    /// Part of the configuration at `path`, which must be an object.
+    pub fn get_config(&self, path: &str) -> Result<Config, Error> {
+        let prefix = self.full_path(path);
+        self.root.get_as(&prefix, |value| {
+            match *value {
+                Value::Object(_) => Ok(()),
+                _ => Err(Error::IncompatibleType)
+            }
+        }).map_err(|err| at_path(&prefix, err))?;
+        Ok(Config { root: self.root.clone(), prefix })
+    }
+
+    /// Full path from the root of this part, empty for the root itself.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Object this part of the configuration is made of.
+    pub fn value(&self) -> &Value {
+        self.root.get_ref(&self.prefix).expect("a config's prefix points to an object")
+    }
+
+    /// Whether `path` is set to something other than `null`.
+    pub fn has_path(&self, path: &str) -> bool {
+        match self.get_ref(path) {
+            Ok(&Value::Null) | Err(_) => false,
+            Ok(_) => true
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys().next().is_none()
+    }
+
+    /// Keys directly in this part, in order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        let fields = match *self.value() {
+            Value::Object(ref obj) => Some(obj.keys().map(String::as_str)),
+            _ => None
+        };
+        fields.into_iter().flatten()
+    }
+
+    /// Every path leading to a value that isn't an object, with that value.
+    /// Arrays are not looked into, and `null` values are left out like with
+    /// `has_path`. Keys are quoted in the paths when they have to be.
+    pub fn entry_set(&self) -> Vec<(String, &Value)> {
+        let mut entries = Vec::new();
+        leaves(self.value(), &mut Vec::new(), &mut entries);
+        entries
+    }
+
+    delegate! {
+        fn get(&self, path: &str) -> Result<Value, Error>;
+        fn get_ref(&self, path: &str) -> Result<&Value, Error>;
+        fn origin(&self, path: &str) -> Option<&Origin>;
+        fn get_bool(&self, path: &str) -> Result<bool, Error>;
+        fn get_bool_or(&self, path: &str, default: bool) -> bool;
+        fn get_int(&self, path: &str) -> Result<i64, Error>;
+        fn get_int_or(&self, path: &str, default: i64) -> i64;
+        fn get_float(&self, path: &str) -> Result<f64, Error>;
+        fn get_float_or(&self, path: &str, default: f64) -> f64;
+        fn get_string(&self, path: &str) -> Result<String, Error>;
+        fn get_string_or(&self, path: &str, default: &str) -> String;
+        fn get_str(&self, path: &str) -> Result<&str, Error>;
+        fn get_duration(&self, path: &str) -> Result<Duration, Error>;
+        fn get_duration_or(&self, path: &str, default: Duration) -> Duration;
+        fn get_bytes(&self, path: &str) -> Result<u64, Error>;
+        fn get_bytes_or(&self, path: &str, default: u64) -> u64;
+        fn get_list(&self, path: &str) -> Result<Vec<Value>, Error>;
+        fn get_bool_list(&self, path: &str) -> Result<Vec<bool>, Error>;
+        fn get_int_list(&self, path: &str) -> Result<Vec<i64>, Error>;
+        fn get_float_list(&self, path: &str) -> Result<Vec<f64>, Error>;
+        fn get_string_list(&self, path: &str) -> Result<Vec<String>, Error>;
+        fn get_duration_list(&self, path: &str) -> Result<Vec<Duration>, Error>;
+        fn get_bytes_list(&self, path: &str) -> Result<Vec<u64>, Error>;
+        fn get_object_list(&self, path: &str) -> Result<Vec<Value>, Error>;
+    }
+
+    fn full_path(&self, path: &str) -> String {
+        if self.prefix.is_empty() {
+            path.to_string()
+        } else if path.is_empty() {
+            self.prefix.clone()
+        } else {
+            format!("{}.{}", self.prefix, path)
+        }
+    }
+
+}
+
+/// Adds the full path to errors that don't name one, as the path given to a
+/// part of the configuration alone doesn't say where the value is.
+fn at_path(path: &str, err: Error) -> Error {
+    let named = match err {
+        Error::AtOrigin(ref err, _) => **err != Error::IncompatibleType,
+        ref err => *err != Error::IncompatibleType
+    };
+    if named {
+        err
+    } else {
+        Error::AtPath(path.to_string(), Box::new(err))
+    }
+}
+
+fn leaves<'a>(value: &'a Value, path: &mut Vec<String>, out: &mut Vec<(String, &'a Value)>) {
+    match *value {
+        Value::Object(ref obj) => {
+            for (key, value) in obj {
+                path.push(key.clone());
+                leaves(value, path, out);
+                path.pop();
+            }
+        },
+        Value::Null => {},
+        _ => out.push((render_path(path), value))
+    }
+}
@@ -0,0 +1,411 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::slice;
+use indexmap::map;
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use ::error::Error;
+use ::value::Value;
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error::Deserialize(String::new(), msg.to_string())
+    }
+}
+
+fn type_error(expected: &str) -> Error {
+    Error::Deserialize(String::new(), format!("expected {}", expected))
+}
+
+/// Adds the key or array index an error happened under to the front of its path.
+fn under(err: Error, segment: String) -> Error {
+    match err {
+        Error::Deserialize(path, msg) => {
+            let separator = if path.is_empty() || path.starts_with('[') { "" } else { "." };
+            Error::Deserialize(format!("{}{}{}", segment, separator, path), msg)
+        },
+        err => err
+    }
+}
+
+fn key_segment(key: &str) -> String {
+    if key.is_empty() || key.contains('.') || key.contains('"') {
+        format!("{:?}", key)
+    } else {
+        key.to_string()
+    }
+}
+
+impl Value {
+
+    /// Numbers may also be given as strings, as HOCON converts between them.
+    fn integer(&self) -> Result<i64, Error> {
+        match *self {
+            Value::Int(i) => Ok(i),
+            Value::String(ref s) => s.trim().parse().map_err(|_| type_error("integer")),
+            _ => Err(type_error("integer"))
+        }
+    }
+
+    fn float(&self) -> Result<f64, Error> {
+        match *self {
+            Value::Float(f) => Ok(f),
+            Value::Int(i) => Ok(i as f64),
+            Value::String(ref s) => s.trim().parse().map_err(|_| type_error("number")),
+            _ => Err(type_error("number"))
+        }
+    }
+
+    fn boolean(&self) -> Result<bool, Error> {
+        match *self {
+            Value::Boolean(b) => Ok(b),
+            Value::String(ref s) if s == "true" || s == "yes" || s == "on" => Ok(true),
+            Value::String(ref s) if s == "false" || s == "no" || s == "off" => Ok(false),
+            _ => Err(type_error("boolean"))
+        }
+    }
+
+    /// Numbers and booleans are accepted where a string is expected.
+    fn string(&self) -> Result<String, Error> {
+        match *self {
+            Value::String(ref s) => Ok(s.clone()),
+            Value::Int(i) => Ok(i.to_string()),
+            Value::Float(f) => Ok(f.to_string()),
+            Value::Boolean(b) => Ok(b.to_string()),
+            _ => Err(type_error("string"))
+        }
+    }
+
+}
+
+macro_rules! deserialize_integer {
+    ($($method:ident => $visit:ident($ty:ty)),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                let i = self.integer()?;
+                let n = <$ty>::try_from(i).map_err(|_| {
+                    Error::Deserialize(String::new(), format!("integer {} is out of range for {}", i, stringify!($ty)))
+                })?;
+                visitor.$visit(n)
+            }
+        )*
+    }
+}
+
+/// Deserializes a value the way it reads in a config: strings holding numbers
+/// or booleans are converted, and `null` is `None`. Errors carry the path of the
+/// field that failed, like `server.listeners[2].port: expected integer`.
+impl<'de> de::Deserializer<'de> for &Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match *self {
+            Value::Null => visitor.visit_unit(),
+            Value::Boolean(b) => visitor.visit_bool(b),
+            Value::Int(i) => visitor.visit_i64(i),
+            Value::Float(f) => visitor.visit_f64(f),
+            Value::String(ref s) => visitor.visit_str(s),
+            Value::Array(_) => self.deserialize_seq(visitor),
+            Value::Object(_) => self.deserialize_map(visitor)
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_bool(self.boolean()?)
+    }
+
+    deserialize_integer!(
+        deserialize_i8 => visit_i8(i8),
+        deserialize_i16 => visit_i16(i16),
+        deserialize_i32 => visit_i32(i32),
+        deserialize_i64 => visit_i64(i64),
+        deserialize_u8 => visit_u8(u8),
+        deserialize_u16 => visit_u16(u16),
+        deserialize_u32 => visit_u32(u32),
+        deserialize_u64 => visit_u64(u64)
+    );
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_f32(self.float()? as f32)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_f64(self.float()?)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let s = self.string().map_err(|_| type_error("character"))?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(type_error("character"))
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.string()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.string()?)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match *self {
+            Value::Array(_) => self.deserialize_seq(visitor),
+            _ => visitor.visit_byte_buf(self.string()?.into_bytes())
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match *self {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match *self {
+            Value::Null => visitor.visit_unit(),
+            _ => Err(type_error("null"))
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match *self {
+            Value::Array(ref elems) => {
+                let mut access = SeqAccess { elems: elems.iter(), index: 0 };
+                let value = visitor.visit_seq(&mut access)?;
+                match access.elems.len() {
+                    0 => Ok(value),
+                    _ => Err(de::Error::invalid_length(elems.len(), &"fewer elements"))
+                }
+            },
+            _ => Err(type_error("array"))
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match *self {
+            Value::Object(ref obj) => visitor.visit_map(MapAccess { entries: obj.iter(), value: None }),
+            _ => Err(type_error("object"))
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V
+    ) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+
+    /// Unit variants are written as strings, other variants as an object
+    /// with the variant name as its only key.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V
+    ) -> Result<V::Value, Error> {
+        match *self {
+            Value::String(ref s) => visitor.visit_enum(s.as_str().into_deserializer()),
+            Value::Object(ref obj) if obj.len() == 1 => {
+                let (variant, value) = obj.iter().next().unwrap();
+                visitor.visit_enum(EnumAccess { variant, value })
+            },
+            _ => Err(type_error("string or object with a single key"))
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+}
+
+macro_rules! forward_to_ref {
+    ($($method:ident),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                (&self).$method(visitor)
+            }
+        )*
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    forward_to_ref!(
+        deserialize_any, deserialize_bool, deserialize_i8, deserialize_i16, deserialize_i32,
+        deserialize_i64, deserialize_u8, deserialize_u16, deserialize_u32, deserialize_u64,
+        deserialize_f32, deserialize_f64, deserialize_char, deserialize_str, deserialize_string,
+        deserialize_bytes, deserialize_byte_buf, deserialize_option, deserialize_unit,
+        deserialize_seq, deserialize_map, deserialize_identifier, deserialize_ignored_any
+    );
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        (&self).deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        (&self).deserialize_newtype_struct(name, visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        (&self).deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V
+    ) -> Result<V::Value, Error> {
+        (&self).deserialize_tuple_struct(name, len, visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V
+    ) -> Result<V::Value, Error> {
+        (&self).deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V
+    ) -> Result<V::Value, Error> {
+        (&self).deserialize_enum(name, variants, visitor)
+    }
+}
+
+struct SeqAccess<'a> {
+    elems: slice::Iter<'a, Value>,
+    index: usize
+}
+
+impl<'a, 'de> de::SeqAccess<'de> for SeqAccess<'a> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        match self.elems.next() {
+            Some(elem) => {
+                let index = self.index;
+                self.index += 1;
+                seed.deserialize(elem).map(Some).map_err(|e| under(e, format!("[{}]", index)))
+            },
+            None => Ok(None)
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.elems.len())
+    }
+}
+
+struct MapAccess<'a> {
+    entries: map::Iter<'a, String, Value>,
+    value: Option<(&'a String, &'a Value)>
+}
+
+impl<'a, 'de> de::MapAccess<'de> for MapAccess<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some((key, value));
+                seed.deserialize(key.as_str().into_deserializer()).map(Some)
+                    .map_err(|e| under(e, key_segment(key)))
+            },
+            None => Ok(None)
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (key, value) = self.value.take().expect("next_value_seed called before next_key_seed");
+        seed.deserialize(value).map_err(|e| under(e, key_segment(key)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct EnumAccess<'a> {
+    variant: &'a String,
+    value: &'a Value
+}
+
+impl<'a, 'de> de::EnumAccess<'de> for EnumAccess<'a> {
+    type Error = Error;
+    type Variant = VariantAccess<'a>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, VariantAccess<'a>), Error> {
+        let variant = seed.deserialize(self.variant.as_str().into_deserializer())?;
+        Ok((variant, VariantAccess { variant: self.variant, value: self.value }))
+    }
+}
+
+struct VariantAccess<'a> {
+    variant: &'a String,
+    value: &'a Value
+}
+
+impl<'a, 'de> de::VariantAccess<'de> for VariantAccess<'a> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserialize::deserialize(self.value).map_err(|e| under(e, key_segment(self.variant)))
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self.value).map_err(|e| under(e, key_segment(self.variant)))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self.value, visitor).map_err(|e| under(e, key_segment(self.variant)))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self.value, visitor).map_err(|e| under(e, key_segment(self.variant)))
+    }
+}
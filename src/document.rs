@@ -0,0 +1,377 @@
+use std::fmt;
+use std::ptr;
+use std::str::FromStr;
+use nom::IResult;
+use ::error::Error;
+use ::options::RenderOptions;
+use ::parser::{json_object_path, skip_whitespace, Field, Node, Span};
+use ::render::{render_path, render_value};
+use ::value::Value;
+
+/// Document kept exactly as it was written, for tools that edit a config
+/// file and write it back. Whitespace, comments, separators and quoting are
+/// kept, and text outside of the fields being edited is left byte for byte
+/// as it was.
+#[derive(Debug, Clone)]
+pub struct Document {
+    text: String,
+    /// Fields of the root object, pointing into `text`.
+    root: Body
+}
+
+/// Fields between braces, or of a root object written without them.
+#[derive(Debug, Clone)]
+struct Body {
+    /// Offset just after the opening brace, or of the start of the document.
+    start: usize,
+    /// Offset of the closing brace, or of the end of the document.
+    end: usize,
+    fields: Vec<FieldSyntax>
+}
+
+#[derive(Debug, Clone)]
+struct FieldSyntax {
+    /// Offset of the key, or of the `include` keyword.
+    start: usize,
+    /// Offset just after the key. Between it and `value_start` are the separator and the whitespace around it.
+    key_end: usize,
+    value_start: usize,
+    /// Offset just after the value.
+    end: usize,
+    /// Keys the field sets, empty for includes.
+    path: Vec<String>,
+    append: bool,
+    /// Fields of the value, when it is a single object.
+    body: Option<Body>
+}
+
+impl Document {
+
+    pub fn parse(s: &str) -> Result<Document, Error> {
+        Ok(Document { root: root_body(s)?, text: s.to_string() })
+    }
+
+    /// Source text of the document, including the changes made to it.
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Resolves the document into a value.
+    pub fn to_value(&self) -> Result<Value, Error> {
+        ::from_str(&self.text)
+    }
+
+    /// Changes the value of the last field setting `path`, keeping its key,
+    /// separator and the comments around it. A field written `path += value`
+    /// becomes `path = value`. If no field sets the path, one is inserted as
+    /// with `insert`. The other fields setting the path or a path inside it
+    /// are removed, so that an object isn't merged with the ones set before.
+    pub fn set(&mut self, path: &str, value: &Value) -> Result<(), Error> {
+        let path = parse_path(path)?;
+        let original = self.clone();
+        let result = self.remove_inside(&path, true).and_then(|_| self.set_last(&path, value));
+        if result.is_err() {
+            *self = original;
+        }
+        result
+    }
+
+    /// Adds a field setting `path` after the fields of the innermost object
+    /// written in the document that the path goes through, following the
+    /// layout of the fields around it. If the path is already set, the new
+    /// field overrides it.
+    pub fn insert(&mut self, path: &str, value: &Value) -> Result<(), Error> {
+        let path = parse_path(path)?;
+        self.insert_path(&path, value)
+    }
+
+    /// Removes every field setting `path` or a path inside it, along with the
+    /// separators that go with them. Comments on the lines above a removed
+    /// field are kept. Returns `NotFound` if no field sets the path.
+    pub fn remove(&mut self, path: &str) -> Result<(), Error> {
+        let keys = parse_path(path)?;
+        let original = self.clone();
+        match self.remove_inside(&keys, false) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(Error::NotFound(path.to_string())),
+            Err(err) => {
+                *self = original;
+                Err(err)
+            }
+        }
+    }
+
+    fn set_last(&mut self, path: &[String], value: &Value) -> Result<(), Error> {
+        let (key_end, value_start, end, append, start) = {
+            let mut found = Vec::new();
+            setting(&self.root, path, &mut found);
+            match found.last() {
+                Some(field) => (field.key_end, field.value_start, field.end, field.append, field.start),
+                None => return self.insert_path(path, value)
+            }
+        };
+        let separator = &self.text[key_end..value_start];
+        let separator = match *value {
+            _ if append => separator.replacen("+=", "=", 1),
+            // `key { ... }` needs a separator once the value isn't an object anymore
+            Value::Object(_) => separator.to_string(),
+            _ if separator.trim().is_empty() => " = ".to_string(),
+            _ => separator.to_string()
+        };
+        let rendered = format!("{}{}", separator, self.render_at(value, start, self.alone(start, end)));
+        self.replace(key_end, end, &rendered)
+    }
+
+    /// Removes the fields setting `path` or a path inside it, except for the
+    /// last one setting exactly `path` if `keep_last` is true. Returns whether
+    /// any field was removed.
+    fn remove_inside(&mut self, path: &[String], keep_last: bool) -> Result<bool, Error> {
+        let mut removed = false;
+        loop {
+            // removing a field moves the ones after it, so they are found again every time
+            let range = {
+                let mut setting_path = Vec::new();
+                if keep_last {
+                    setting(&self.root, path, &mut setting_path);
+                }
+                let kept = setting_path.last();
+                let mut found = Vec::new();
+                inside(&self.root, path, &mut found);
+                let removable = found.into_iter().rev()
+                    .find(|&(body, i)| kept.is_none_or(|&kept| !ptr::eq(kept, &body.fields[i])));
+                match removable {
+                    Some((body, i)) => self.removal(body, i),
+                    None => break
+                }
+            };
+            self.replace(range.0, range.1, "")?;
+            removed = true;
+        }
+        Ok(removed)
+    }
+
+    fn insert_path(&mut self, path: &[String], value: &Value) -> Result<(), Error> {
+        let (start, end, text) = {
+            let (body, depth) = innermost(&self.root, path);
+            let separator = body.fields.iter().rev()
+                .find(|f| !f.path.is_empty() && !f.append && f.body.is_none())
+                .map_or(" = ", |f| &self.text[f.key_end..f.value_start]);
+            let key = render_path(&path[depth..]);
+            match body.fields.last() {
+                Some(last) => {
+                    let line_start = self.line_start(last.start);
+                    if self.text[line_start..last.start].trim().is_empty() {
+                        // one field per line: add a line after the last one, past any comment ending it
+                        let indent = &self.text[line_start..last.start];
+                        let position = if self.alone(last.start, last.end) { self.line_end(last.end) } else { last.end };
+                        let field = format!("{}{}{}", key, separator, self.render_at(value, last.start, true));
+                        (position, position, format!("\n{}{}", indent, field))
+                    } else {
+                        let field = format!("{}{}{}", key, separator, self.render_at(value, last.start, false));
+                        (last.end, last.end, format!(", {}", field))
+                    }
+                },
+                None => {
+                    let inside = &self.text[body.start..body.end];
+                    if body.end == self.text.len() && body.start == 0 {
+                        // a root without fields: add a line at the end
+                        let newline = if self.text.is_empty() || self.text.ends_with('\n') { "" } else { "\n" };
+                        let field = format!("{}{}{}", key, separator, render_value(value, &RenderOptions::new()));
+                        (body.end, body.end, format!("{}{}\n", newline, field))
+                    } else if inside.contains('\n') {
+                        let indent = format!("{}  ", self.indent_of(body.start - 1));
+                        let field = format!("{}{}{}", key, separator, render_value(value, &RenderOptions::new()));
+                        (body.start, body.start, format!("\n{}{}", indent, field.replace('\n', &format!("\n{}", indent))))
+                    } else {
+                        let options = RenderOptions::new().compact(true);
+                        let field = format!("{}{}{}", key, separator, render_value(value, &options));
+                        // the braces hold nothing but spaces, which the field replaces
+                        (body.start, body.end, format!(" {} ", field))
+                    }
+                }
+            }
+        };
+        self.replace(start, end, &text)
+    }
+
+    /// Range of text to remove with the `i`th field of `body`.
+    fn removal(&self, body: &Body, i: usize) -> (usize, usize) {
+        let field = &body.fields[i];
+        let line_start = self.line_start(field.start);
+        let line_end = self.line_end(field.end);
+        if self.alone(field.start, field.end) {
+            // the field has its lines to itself, along with any comment ending them
+            if line_end < self.text.len() {
+                (line_start, line_end + 1)
+            } else {
+                (line_start.saturating_sub(1), line_end)
+            }
+        } else if let Some(next) = body.fields.get(i + 1).filter(|next| {
+            self.text[line_start..field.start].trim().is_empty() && next.start <= line_end
+        }) {
+            // the field starts its line and another one follows it there
+            (field.start, next.start)
+        } else if i > 0 {
+            (body.fields[i - 1].end, field.end)
+        } else if let Some(next) = body.fields.get(1) {
+            (field.start, next.start)
+        } else {
+            (field.start, field.end)
+        }
+    }
+
+    /// Whether the text from `start` to `end` has its lines to itself, apart from comments.
+    fn alone(&self, start: usize, end: usize) -> bool {
+        let line_start = self.line_start(start);
+        let line_end = self.line_end(end);
+        self.text[line_start..start].trim().is_empty() &&
+            skip_whitespace(&self.text.as_bytes()[end..line_end]).is_empty()
+    }
+
+    /// Renders a value set on the field starting at `start`, indenting the
+    /// lines after the first like the field. A field sharing its lines with
+    /// others gets the value on a single line.
+    fn render_at(&self, value: &Value, start: usize, alone: bool) -> String {
+        if !alone {
+            return render_value(value, &RenderOptions::new().compact(true));
+        }
+        let indent = self.indent_of(start);
+        render_value(value, &RenderOptions::new()).replace('\n', &format!("\n{}", indent))
+    }
+
+    /// Replaces a range of the text and parses it again, leaving the document
+    /// as it was if the new text doesn't parse.
+    fn replace(&mut self, start: usize, end: usize, with: &str) -> Result<(), Error> {
+        let mut text = self.text.clone();
+        text.replace_range(start..end, with);
+        self.root = root_body(&text)?;
+        self.text = text;
+        Ok(())
+    }
+
+    fn line_start(&self, offset: usize) -> usize {
+        self.text[..offset].rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    fn line_end(&self, offset: usize) -> usize {
+        self.text[offset..].find('\n').map_or(self.text.len(), |i| offset + i)
+    }
+
+    /// Whitespace at the start of the line holding `offset`.
+    fn indent_of(&self, offset: usize) -> &str {
+        let line = &self.text[self.line_start(offset)..];
+        &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+    }
+
+}
+
+impl FromStr for Document {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Document, Error> {
+        Document::parse(s)
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+fn parse_path(path: &str) -> Result<Vec<String>, Error> {
+    match json_object_path(path.as_bytes()) {
+        IResult::Done(&[], keys) if !keys.is_empty() => Ok(keys),
+        _ => Err(Error::PathError(path.to_string()))
+    }
+}
+
+/// Fields setting exactly `path`, in document order.
+fn setting<'a>(body: &'a Body, path: &[String], found: &mut Vec<&'a FieldSyntax>) {
+    for field in &body.fields {
+        if field.path == path {
+            found.push(field);
+        } else if let Some(ref inner) = field.body {
+            if !field.path.is_empty() && path.starts_with(&field.path) {
+                setting(inner, &path[field.path.len()..], found);
+            }
+        }
+    }
+}
+
+/// Fields setting `path` or a path inside it, with the body holding them and their position in it.
+fn inside<'a>(body: &'a Body, path: &[String], found: &mut Vec<(&'a Body, usize)>) {
+    for (i, field) in body.fields.iter().enumerate() {
+        if field.path.is_empty() {
+            continue;
+        }
+        if field.path.starts_with(path) {
+            found.push((body, i));
+        } else if let Some(ref inner) = field.body {
+            if path.starts_with(&field.path) {
+                inside(inner, &path[field.path.len()..], found);
+            }
+        }
+    }
+}
+
+/// Innermost object written in the document that `path` goes through, with
+/// the number of keys of the path it covers.
+fn innermost<'a>(body: &'a Body, path: &[String]) -> (&'a Body, usize) {
+    let outer = body.fields.iter().rev().find(|f| {
+        f.body.is_some() && !f.path.is_empty() && f.path.len() < path.len() && path.starts_with(&f.path)
+    });
+    match outer {
+        Some(field) => {
+            let (inner, depth) = innermost(field.body.as_ref().unwrap(), &path[field.path.len()..]);
+            (inner, depth + field.path.len())
+        },
+        None => (body, 0)
+    }
+}
+
+/// Parses a document and finds the fields of its root object.
+fn root_body(text: &str) -> Result<Body, Error> {
+    let input = text.as_bytes();
+    let fields = match ::parse(input, "<string>")? {
+        Node::Object(fields) => fields,
+        _ => Vec::new()
+    };
+    let i = skip_whitespace(input);
+    if i.starts_with(b"{") {
+        Ok(body(input, input.len() - i.len() + 1, &fields))
+    } else {
+        Ok(Body { start: 0, end: input.len(), fields: syntax(input, &fields) })
+    }
+}
+
+/// Fields of an object written between braces, `start` being just after the opening one.
+fn body(input: &[u8], start: usize, fields: &[Field]) -> Body {
+    let fields = syntax(input, fields);
+    // only whitespace is left between the last field and the closing brace
+    let after = fields.last().map_or(start, |field| field.end);
+    let end = input.len() - skip_whitespace(&input[after..]).len();
+    Body { start, end, fields }
+}
+
+/// Where the fields parsed from `input` were written.
+fn syntax(input: &[u8], fields: &[Field]) -> Vec<FieldSyntax> {
+    // spans count the input left, so they are offsets from the end of the document
+    let offsets = |span: &Span| {
+        (input.len() - span.start, input.len() - span.key_end, input.len() - span.value_start, input.len() - span.end)
+    };
+    fields.iter().map(|field| {
+        let (path, value, span, append) = match *field {
+            Field::Pair(ref path, ref value, ref span) => (path.clone(), Some(value), span, false),
+            Field::Append(ref path, ref value, ref span) => (path.clone(), Some(value), span, true),
+            Field::Include(_, ref span) => (Vec::new(), None, span, false)
+        };
+        let (start, key_end, value_start, end) = offsets(span);
+        // only a value that is a single object has fields that can be edited
+        let body = match value {
+            Some(Node::Object(fields)) => Some(body(input, value_start + 1, fields)),
+            _ => None
+        };
+        FieldSyntax { start, key_end, value_start, end, path, append, body }
+    }).collect()
+}
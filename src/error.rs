@@ -1,16 +1,84 @@
-use nom;
+use std::fmt;
+use ::origin::Origin;
+
+/// Position of a syntax error in a document, with the kinds of tokens that
+/// would have been accepted there.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    /// Name of the file or include the document came from, `<string>` otherwise.
+    pub origin: String,
+    /// Byte offset from the start of the document.
+    pub offset: usize,
+    /// Line number, starting at 1.
+    pub line: usize,
+    /// Column in characters, starting at 1.
+    pub column: usize,
+    pub expected: Vec<&'static str>,
+    source_line: String
+}
+
+impl Diagnostic {
+
+    pub(crate) fn new(origin: &str, input: &[u8], offset: usize, expected: Vec<&'static str>) -> Diagnostic {
+        let offset = offset.min(input.len());
+        let line_start = input[..offset].iter().rposition(|&c| c == b'\n').map_or(0, |i| i + 1);
+        let line_end = input[offset..].iter().position(|&c| c == b'\n').map_or(input.len(), |i| offset + i);
+        let before = String::from_utf8_lossy(&input[line_start..offset]);
+        Diagnostic {
+            origin: origin.to_string(),
+            offset,
+            line: input[..offset].iter().filter(|&&c| c == b'\n').count() + 1,
+            column: before.chars().count() + 1,
+            expected,
+            source_line: String::from_utf8_lossy(&input[line_start..line_end]).trim_end_matches('\r').to_string()
+        }
+    }
+
+    /// Describes what was expected, like `expected one of ':', '=' or '{'`.
+    pub fn expected_message(&self) -> String {
+        match self.expected.split_last() {
+            None => "unexpected input".to_string(),
+            Some((last, [])) => format!("expected {}", last),
+            Some((last, rest)) => format!("expected one of {} or {}", rest.join(", "), last)
+        }
+    }
+
+    /// Renders `message` with the offending line and a caret under the error position.
+    pub fn render(&self, message: &str) -> String {
+        let line = self.line.to_string();
+        let margin = " ".repeat(line.len());
+        let indent: String = self.source_line.chars().take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        format!(
+            "error: {}\n{}--> {}:{}:{}\n{} |\n{} | {}\n{} | {}^",
+            message, margin, self.origin, self.line, self.column,
+            margin, line, self.source_line, margin, indent
+        )
+    }
+
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.render(&self.expected_message()))
+    }
+}
 
 quick_error! {
     #[derive(Debug, PartialEq)]
     pub enum Error {
-        ExtraInput(i: isize) {
+        ExtraInput(at: Diagnostic) {
             description("extra input was left after parsing")
+            display("{}", at)
         }
-        IncompleteInput(err: nom::Needed) {
-            description("not enough input to parse")
+        InvalidEscape(at: Diagnostic) {
+            description("invalid escape sequence in a quoted string")
+            display("{}", at.render("invalid escape sequence"))
         }
-        ParseError(err: String) {
+        ParseError(at: Diagnostic) {
             description("error during parsing")
+            display("{}", at)
         }
         PathError(path: String) {
             description("incorrect path expression")
@@ -18,6 +86,62 @@ quick_error! {
         NotFound(path: String) {
             description("path not found in config")
         }
+        IndexOutOfBounds(path: String, index: i64) {
+            description("array index out of bounds")
+            display("index {} is out of bounds in {}", index, path)
+        }
+        UnresolvedSubstitution(path: String) {
+            description("substitution could not be resolved")
+            display("substitution ${{{}}} could not be resolved", path)
+        }
+        SubstitutionCycle(paths: Vec<String>) {
+            description("substitutions refer to each other in a cycle")
+            display("substitutions refer to each other in a cycle: {}", paths.join(" -> "))
+        }
+        ConcatenationMismatch(first: &'static str, second: &'static str) {
+            description("values of incompatible types are concatenated")
+            display("cannot concatenate {} with {}", first, second)
+        }
+        Io(path: String, err: String) {
+            description("could not read file")
+            display("could not read {}: {}", path, err)
+        }
+        IncludeNotFound(name: String) {
+            description("required include was not found")
+            display("required include {} was not found", name)
+        }
+        IncludeCycle(name: String) {
+            description("document includes itself")
+            display("{} includes itself", name)
+        }
         IncompatibleType
+        UnknownUnit(path: String, unit: String) {
+            description("unknown unit")
+            display("unknown unit '{}' at {}", unit, path)
+        }
+        ListElement(path: String, index: usize, err: Box<Error>) {
+            description("list element has the wrong type")
+            display("element {} of {}: {}", index, path, err)
+        }
+        OutOfRange(path: String) {
+            description("value is out of range")
+            display("value at {} is out of range", path)
+        }
+        AtPath(path: String, err: Box<Error>) {
+            description("value at a path could not be converted")
+            display("{}: {}", path, err)
+        }
+        AtOrigin(err: Box<Error>, origin: Origin) {
+            description("value could not be converted")
+            display("{} (set at {})", err, origin)
+        }
+        Deserialize(path: String, message: String) {
+            description("value could not be deserialized")
+            display("{}{}{}", path, if path.is_empty() { "" } else { ": " }, message)
+        }
+        Serialize(message: String) {
+            description("value could not be serialized")
+            display("{}", message)
+        }
     }
 }
@@ -0,0 +1,93 @@
+use nom::IResult;
+use ::error::Error;
+use ::options::FlattenOptions;
+use ::parser::json_object_path;
+use ::render::render_path;
+use ::value::{object_fields, Map, Value};
+
+impl Value {
+
+    /// Lists every value that isn't an object with its path, in order, for
+    /// systems that only know flat key-value pairs. Keys are quoted in paths
+    /// when they have to be, like `"a.b".c`. Empty objects are kept as values,
+    /// so `from_flat` gives back the same value.
+    pub fn flatten(&self) -> Vec<(String, Value)> {
+        self.flatten_with_options(&FlattenOptions::default())
+    }
+
+    pub fn flatten_with_options(&self, options: &FlattenOptions) -> Vec<(String, Value)> {
+        let mut entries = Vec::new();
+        flatten_into(self, options, &mut Vec::new(), &mut entries);
+        entries
+    }
+
+    /// Rebuilds nested objects from paths and values, the reverse of `flatten`.
+    /// A value set at a path inside one set before replaces it, as in a document.
+    pub fn from_flat<I: IntoIterator<Item = (String, Value)>>(entries: I) -> Result<Value, Error> {
+        Value::from_flat_with_options(entries, &FlattenOptions::default())
+    }
+
+    pub fn from_flat_with_options<I>(entries: I, options: &FlattenOptions) -> Result<Value, Error>
+        where I: IntoIterator<Item = (String, Value)>
+    {
+        let mut root = Value::Object(Map::new());
+        for (path, value) in entries {
+            // the empty path is the root, when it isn't an object
+            if path.is_empty() {
+                root = value;
+                continue;
+            }
+            let keys = match json_object_path(path.as_bytes()) {
+                IResult::Done(&[], ref keys) if !keys.is_empty() => keys.clone(),
+                _ => return Err(Error::PathError(path))
+            };
+            let (last, parents) = keys.split_last().unwrap();
+            let parent = parents.iter().fold(&mut root, |v, key| {
+                object_fields(v).get_or_insert_with(key.clone(), || Value::Object(Map::new()))
+            });
+            object_fields(parent).insert(last.clone(), value);
+        }
+        if options.indexed_arrays {
+            root = arrays_from_indices(root);
+        }
+        Ok(root)
+    }
+
+}
+
+fn flatten_into(value: &Value, options: &FlattenOptions, path: &mut Vec<String>, out: &mut Vec<(String, Value)>) {
+    match *value {
+        Value::Object(ref obj) if !obj.is_empty() => {
+            for (key, value) in obj {
+                path.push(key.clone());
+                flatten_into(value, options, path, out);
+                path.pop();
+            }
+        },
+        Value::Array(ref elems) if options.indexed_arrays && !elems.is_empty() => {
+            for (i, elem) in elems.iter().enumerate() {
+                path.push(i.to_string());
+                flatten_into(elem, options, path, out);
+                path.pop();
+            }
+        },
+        _ => out.push((render_path(path), value.clone()))
+    }
+}
+
+/// Turns objects whose keys are exactly `0` to `n - 1` into arrays, innermost first.
+fn arrays_from_indices(value: Value) -> Value {
+    match value {
+        Value::Object(obj) => {
+            let obj: Map = obj.into_iter().map(|(key, value)| (key, arrays_from_indices(value))).collect();
+            let indexed = !obj.is_empty() && (0..obj.len()).all(|i| obj.contains_key(&i.to_string()));
+            if indexed {
+                let mut obj = obj;
+                Value::Array((0..obj.len()).map(|i| obj.shift_remove(&i.to_string()).unwrap()).collect())
+            } else {
+                Value::Object(obj)
+            }
+        },
+        value => value
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use ::error::Error;
+use ::options::ParseOptions;
+use ::parser::{Field, Include, IncludeKind, Node, Span};
+use ::value::Value;
+
+/// Content of an included document.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Included {
+    /// Source text that still has to be parsed.
+    Bytes(Vec<u8>),
+    /// Already parsed document, which must be an object.
+    Value(Value)
+}
+
+/// Source of the documents named by `include` statements.
+pub trait Includer {
+
+    /// Name of the document an include statement refers to, given the name of
+    /// the including document if there is one. Names are kept as they are by default.
+    fn locate(&self, _kind: IncludeKind, name: &str, _from: Option<&str>) -> String {
+        name.to_string()
+    }
+
+    /// Loads a document by the name `locate` returned, or `None` if it doesn't exist.
+    fn load(&self, kind: IncludeKind, name: &str) -> Result<Option<Included>, Error>;
+
+}
+
+/// Reads files relative to the including file, and classpath includes
+/// from a list of root directories. Url includes are not supported.
+#[derive(Debug, Clone, Default)]
+pub struct FileIncluder {
+    classpath: Vec<PathBuf>
+}
+
+impl FileIncluder {
+
+    pub fn new() -> FileIncluder {
+        FileIncluder::default()
+    }
+
+    /// Adds a directory searched by `include classpath(...)`.
+    pub fn classpath_root<P: AsRef<Path>>(mut self, root: P) -> FileIncluder {
+        self.classpath.push(root.as_ref().to_path_buf());
+        self
+    }
+
+}
+
+impl Includer for FileIncluder {
+
+    fn locate(&self, kind: IncludeKind, name: &str, from: Option<&str>) -> String {
+        match (kind, from) {
+            (IncludeKind::File, Some(from)) => {
+                parent_dir(Path::new(from)).join(name).display().to_string()
+            },
+            _ => name.to_string()
+        }
+    }
+
+    fn load(&self, kind: IncludeKind, name: &str) -> Result<Option<Included>, Error> {
+        match kind {
+            IncludeKind::File => read_file(Path::new(name)),
+            IncludeKind::Classpath => {
+                for root in &self.classpath {
+                    if let Some(included) = read_file(&root.join(name))? {
+                        return Ok(Some(included));
+                    }
+                }
+                Ok(None)
+            },
+            IncludeKind::Url => Ok(None)
+        }
+    }
+
+}
+
+/// Serves every kind of include from the map, by name.
+impl Includer for HashMap<String, String> {
+
+    fn load(&self, _kind: IncludeKind, name: &str) -> Result<Option<Included>, Error> {
+        Ok(self.get(name).map(|s| Included::Bytes(s.clone().into_bytes())))
+    }
+
+}
+
+fn read_file(path: &Path) -> Result<Option<Included>, Error> {
+    match fs::read(path) {
+        Ok(input) => Ok(Some(Included::Bytes(input))),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(ref e) => Err(io_error(path, e))
+    }
+}
+
+/// Reads and parses a file, replacing its includes with the included fields.
+pub fn load_file(path: &Path, options: &ParseOptions) -> Result<Node, Error> {
+    let input = fs::read(path).map_err(|e| io_error(path, &e))?;
+    let name = path.display().to_string();
+    let root = ::parse(&input, &name)?;
+    let mut chain = vec![(IncludeKind::File, name.clone())];
+    expand_node(root, Some(&name), &[], options, &mut chain)
+}
+
+/// Replaces includes with the included fields, for a document that has no name.
+pub fn expand(root: Node, options: &ParseOptions) -> Result<Node, Error> {
+    expand_node(root, None, &[], options, &mut Vec::new())
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    path.parent().unwrap_or_else(|| Path::new(""))
+}
+
+fn io_error(path: &Path, err: &io::Error) -> Error {
+    Error::Io(path.display().to_string(), err.to_string())
+}
+
+/// `from` names the document being expanded, and `chain` holds the documents
+/// currently being included, to catch documents including themselves.
+fn expand_node(
+    node: Node,
+    from: Option<&str>,
+    prefix: &[String],
+    options: &ParseOptions,
+    chain: &mut Vec<(IncludeKind, String)>
+) -> Result<Node, Error> {
+    match node {
+        Node::Object(fields) => {
+            let mut expanded = Vec::with_capacity(fields.len());
+            for field in fields {
+                match field {
+                    Field::Pair(path, value, span) => {
+                        let mut full_path = prefix.to_vec();
+                        full_path.extend(path.iter().cloned());
+                        let value = expand_node(value, from, &full_path, options, chain)?;
+                        expanded.push(Field::Pair(path, value, span));
+                    },
+                    Field::Append(path, value, span) => {
+                        let mut full_path = prefix.to_vec();
+                        full_path.extend(path.iter().cloned());
+                        let value = expand_node(value, from, &full_path, options, chain)?;
+                        expanded.push(Field::Append(path, value, span));
+                    },
+                    Field::Include(include, _) => {
+                        if let Some(Node::Object(fields)) = include_document(&include, from, prefix, options, chain)? {
+                            expanded.extend(fields);
+                        }
+                    }
+                }
+            }
+            Ok(Node::Object(expanded))
+        },
+        Node::Array(elems) => {
+            let mut expanded = Vec::with_capacity(elems.len());
+            for elem in elems {
+                expanded.push(expand_node(elem, from, prefix, options, chain)?);
+            }
+            Ok(Node::Array(expanded))
+        },
+        Node::Concatenation(parts) => {
+            let mut expanded = Vec::with_capacity(parts.len());
+            for part in parts {
+                expanded.push(expand_node(part, from, prefix, options, chain)?);
+            }
+            Ok(Node::Concatenation(expanded))
+        },
+        node => Ok(node)
+    }
+}
+
+fn include_document(
+    include: &Include,
+    from: Option<&str>,
+    prefix: &[String],
+    options: &ParseOptions,
+    chain: &mut Vec<(IncludeKind, String)>
+) -> Result<Option<Node>, Error> {
+    let includer = options.includer_or_default();
+    let name = includer.locate(include.kind, &include.name, from);
+    let entry = (include.kind, name);
+    if chain.contains(&entry) {
+        return Err(Error::IncludeCycle(entry.1));
+    }
+    let root = match includer.load(include.kind, &entry.1)? {
+        Some(Included::Bytes(input)) => ::parse(&input, &entry.1)?,
+        Some(Included::Value(value @ Value::Object(_))) => to_node(value),
+        Some(Included::Value(_)) => return Err(Error::IncompatibleType),
+        None if include.required => return Err(Error::IncludeNotFound(entry.1)),
+        None => return Ok(None)
+    };
+    let name = entry.1.clone();
+    chain.push(entry);
+    let root = expand_node(root, Some(&name), &[], options, chain);
+    chain.pop();
+    Ok(Some(relativize(root?, prefix)))
+}
+
+/// Turns a value back into a parse tree, keeping the origins of its fields.
+pub(crate) fn to_node(value: Value) -> Node {
+    match value {
+        Value::Null => Node::Null,
+        Value::Boolean(b) => Node::Boolean(b),
+        Value::Int(i) => Node::Int(i),
+        Value::Float(f) => Node::Float(f),
+        Value::String(s) => Node::String(s),
+        Value::Array(elems) => Node::Array(elems.into_iter().map(to_node).collect()),
+        Value::Object(obj) => {
+            Node::Object(obj.into_entries().into_iter().map(|(key, value, origin)| {
+                Field::Pair(vec![key], to_node(value), Span { origin, ..Span::default() })
+            }).collect())
+        }
+    }
+}
+
+/// Makes the substitutions of a document included at `prefix` relative to that path.
+fn relativize(node: Node, prefix: &[String]) -> Node {
+    match node {
+        Node::Substitution(path, optional) => {
+            let mut full_path = prefix.to_vec();
+            full_path.extend(path);
+            Node::Substitution(full_path, optional)
+        },
+        Node::Object(fields) => {
+            Node::Object(fields.into_iter().map(|field| {
+                match field {
+                    Field::Pair(path, value, span) => Field::Pair(path, relativize(value, prefix), span),
+                    Field::Append(path, value, span) => Field::Append(path, relativize(value, prefix), span),
+                    include => include
+                }
+            }).collect())
+        },
+        Node::Array(elems) => Node::Array(elems.into_iter().map(|e| relativize(e, prefix)).collect()),
+        Node::Concatenation(parts) => {
+            Node::Concatenation(parts.into_iter().map(|p| relativize(p, prefix)).collect())
+        },
+        node => node
+    }
+}
@@ -1,59 +1,90 @@
-#[macro_use] extern crate nom;
+extern crate nom;
+extern crate indexmap;
 #[macro_use] extern crate quick_error;
+#[cfg(feature = "serde")] extern crate serde;
+#[cfg(all(test, feature = "serde"))] #[macro_use] extern crate serde_derive;
 
+#[macro_use] mod macros;
 mod value;
 mod parser;
+mod resolve;
+mod include;
+mod options;
+mod render;
+mod loader;
+mod document;
+mod config;
+mod flat;
 mod error;
+mod origin;
+#[cfg(feature = "serde")] mod de;
+#[cfg(feature = "serde")] mod ser;
 #[cfg(test)] mod tests;
 
-pub use value::Value;
-pub use error::Error;
-use nom::IResult;
+pub use value::{Map, Value};
+pub use indexmap::IndexMap;
+pub use error::{Diagnostic, Error};
+pub use origin::Origin;
+pub use options::{FlattenOptions, ParseOptions, RenderOptions};
+pub use include::{FileIncluder, Included, Includer};
+pub use parser::IncludeKind;
+pub use loader::ConfigLoader;
+pub use document::Document;
+pub use config::Config;
+#[cfg(feature = "serde")] pub use ser::to_value;
+use parser::Node;
+use nom::{ErrorKind, IResult};
 use nom::verbose_errors::Err;
+use std::path::Path;
 
 pub fn from_str(s: &str) -> Result<Value, Error> {
     from_bytes(s.as_bytes())
 }
 
+pub fn from_str_with_options(s: &str, options: &ParseOptions) -> Result<Value, Error> {
+    from_bytes_with_options(s.as_bytes(), options)
+}
+
 pub fn from_bytes(input: &[u8]) -> Result<Value, Error> {
+    from_bytes_with_options(input, &ParseOptions::default())
+}
+
+pub fn from_bytes_with_options(input: &[u8], options: &ParseOptions) -> Result<Value, Error> {
+    let root = include::expand(parse(input, "<string>")?, options)?;
+    resolve::resolve(&root, options)
+}
+
+/// Parses a document and deserializes it into `T`.
+#[cfg(feature = "serde")]
+pub fn from_str_as<T: serde::de::DeserializeOwned>(s: &str) -> Result<T, Error> {
+    T::deserialize(from_str(s)?)
+}
+
+/// Parses a file, reading its includes relative to the file's directory.
+pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Value, Error> {
+    from_file_with_options(path, &ParseOptions::default())
+}
+
+pub fn from_file_with_options<P: AsRef<Path>>(path: P, options: &ParseOptions) -> Result<Value, Error> {
+    let root = include::load_file(path.as_ref(), options)?;
+    resolve::resolve(&root, options)
+}
+
+/// Parses a document, naming it `origin` in syntax errors and in the origins of its fields.
+fn parse(input: &[u8], origin: &str) -> Result<Node, Error> {
     match parser::json_value_root(input) {
-        IResult::Done(rest, value) => {
-            if rest == &b""[..] {
-                Ok(value)
-            } else {
-                Err(Error::ExtraInput(rest.as_ptr() as isize - input.as_ptr() as isize))
-            }
-        },
-        IResult::Incomplete(needed) => {
-            Err(Error::IncompleteInput(needed))
-        },
-        IResult::Error(Err::Code(kind)) => {
-            Err(Error::ParseError(format!("parse error: {}", kind.description())))
-        },
-        IResult::Error(Err::Node(kind, _)) => {
-            Err(Error::ParseError(format!("parse error: {}", kind.description())))
+        IResult::Done(_, mut root) => {
+            origin::locate(&mut root, input, origin);
+            Ok(root)
         },
-        IResult::Error(Err::Position(kind, pos)) => {
-            Err(Error::ParseError(format!(
-                "parse error '{}' at '{}'",
-                kind.description(),
-                if pos.len() > 50 {
-                    format!("{}...", String::from_utf8_lossy(&pos[..50]))
-                } else {
-                    format!("{}", String::from_utf8_lossy(&pos[..50]))
-                }
-            )))
+        IResult::Error(Err::Position(ErrorKind::Custom(code), rest)) => {
+            let offset = input.len() - rest.len();
+            Err(match code {
+                parser::ESCAPE => Error::InvalidEscape(Diagnostic::new(origin, input, offset, Vec::new())),
+                parser::END_OF_INPUT => Error::ExtraInput(Diagnostic::new(origin, input, offset, parser::expected(code))),
+                _ => Error::ParseError(Diagnostic::new(origin, input, offset, parser::expected(code)))
+            })
         },
-        IResult::Error(Err::NodePosition(kind, pos, _)) => {
-            Err(Error::ParseError(format!(
-                "parse error '{}' at '{}...'",
-                kind.description(),
-                if pos.len() > 50 {
-                    format!("{}...", String::from_utf8_lossy(&pos[..50]))
-                } else {
-                    format!("{}", String::from_utf8_lossy(&pos[..50]))
-                }
-            )))
-        }
+        _ => Err(Error::ParseError(Diagnostic::new(origin, input, 0, Vec::new())))
     }
 }
@@ -0,0 +1,82 @@
+use std::path::{Path, PathBuf};
+use ::error::Error;
+use ::include::{self, to_node};
+use ::options::ParseOptions;
+use ::parser::Node;
+use ::value::Value;
+
+enum Source {
+    /// Path of a file, and whether it has to exist.
+    File(PathBuf, bool),
+    Str(String),
+    Value(Value)
+}
+
+/// Loads a configuration from several layers, each overriding the ones added
+/// before it. Substitutions are resolved once all layers are merged, so a
+/// layer can refer to values set by any other.
+#[derive(Default)]
+pub struct ConfigLoader {
+    sources: Vec<Source>,
+    options: ParseOptions
+}
+
+impl ConfigLoader {
+
+    pub fn new() -> ConfigLoader {
+        ConfigLoader::default()
+    }
+
+    /// Adds a file, which must exist.
+    pub fn file<P: AsRef<Path>>(mut self, path: P) -> ConfigLoader {
+        self.sources.push(Source::File(path.as_ref().to_path_buf(), true));
+        self
+    }
+
+    /// Adds a file that is skipped if it doesn't exist.
+    pub fn optional_file<P: AsRef<Path>>(mut self, path: P) -> ConfigLoader {
+        self.sources.push(Source::File(path.as_ref().to_path_buf(), false));
+        self
+    }
+
+    /// Adds a document given as a string.
+    pub fn str(mut self, s: &str) -> ConfigLoader {
+        self.sources.push(Source::Str(s.to_string()));
+        self
+    }
+
+    /// Adds an already built value, which must be an object.
+    pub fn value(mut self, value: Value) -> ConfigLoader {
+        self.sources.push(Source::Value(value));
+        self
+    }
+
+    /// Options used to parse every layer and to resolve the merged result.
+    pub fn options(mut self, options: ParseOptions) -> ConfigLoader {
+        self.options = options;
+        self
+    }
+
+    pub fn load(&self) -> Result<Value, Error> {
+        let mut fields = Vec::new();
+        for source in &self.sources {
+            let root = match *source {
+                Source::File(ref path, required) => {
+                    if !required && !path.exists() {
+                        continue;
+                    }
+                    include::load_file(path, &self.options)?
+                },
+                Source::Str(ref s) => include::expand(::parse(s.as_bytes(), "<string>")?, &self.options)?,
+                Source::Value(ref value) => to_node(value.clone())
+            };
+            match root {
+                // later fields override earlier ones, exactly like in a single document
+                Node::Object(layer) => fields.extend(layer),
+                _ => return Err(Error::IncompatibleType)
+            }
+        }
+        ::resolve::resolve(&Node::Object(fields), &self.options)
+    }
+
+}
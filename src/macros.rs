@@ -0,0 +1,32 @@
+/// Builds a `Value` from a JSON-like literal. Keys are identifiers or string
+/// literals. Inside arrays and objects, a value is a literal, a negative number,
+/// an identifier, nested brackets or braces, or an expression in parentheses;
+/// values that aren't arrays, objects or `null` are converted with `Value::from`.
+#[macro_export]
+macro_rules! hocon {
+    (@key $key:ident) => {
+        String::from(stringify!($key))
+    };
+    (@key $key:expr) => {
+        String::from($key)
+    };
+
+    (null) => {
+        $crate::Value::Null
+    };
+    // a literal can follow the value, for a sign in front of a number
+    ([ $($elem:tt $($literal:literal)?),* $(,)? ]) => {
+        $crate::Value::Array(vec![$($crate::hocon!($elem $($literal)?)),*])
+    };
+    ({ $($key:tt : $value:tt $($literal:literal)?),* $(,)? }) => {{
+        #[allow(unused_mut)]
+        let mut map = $crate::Map::new();
+        $(
+            map.insert($crate::hocon!(@key $key), $crate::hocon!($value $($literal)?));
+        )*
+        $crate::Value::Object(map)
+    }};
+    ($other:expr) => {
+        $crate::Value::from($other)
+    };
+}
@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::sync::Arc;
+use ::include::{FileIncluder, Includer};
+
+/// Settings for parsing and resolving a document.
+#[derive(Clone)]
+pub struct ParseOptions {
+    env_fallback: bool,
+    env: Option<HashMap<String, String>>,
+    includer: Option<Arc<dyn Includer + Send + Sync>>
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            env_fallback: true,
+            env: None,
+            includer: None
+        }
+    }
+}
+
+impl fmt::Debug for ParseOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ParseOptions")
+            .field("env_fallback", &self.env_fallback)
+            .field("env", &self.env)
+            .field("includer", &self.includer.as_ref().map(|_| "<includer>"))
+            .finish()
+    }
+}
+
+impl ParseOptions {
+
+    pub fn new() -> ParseOptions {
+        ParseOptions::default()
+    }
+
+    /// Whether substitutions not found in the document are looked up in the
+    /// environment. Enabled by default.
+    pub fn env_fallback(mut self, enabled: bool) -> ParseOptions {
+        self.env_fallback = enabled;
+        self
+    }
+
+    /// Uses the given variables instead of the process environment.
+    pub fn env(mut self, vars: HashMap<String, String>) -> ParseOptions {
+        self.env = Some(vars);
+        self
+    }
+
+    /// Loads included documents with `includer` instead of a `FileIncluder`.
+    /// The includer is shared by clones of the options, which can be sent to
+    /// other threads.
+    pub fn includer<I: Includer + Send + Sync + 'static>(mut self, includer: I) -> ParseOptions {
+        self.includer = Some(Arc::new(includer));
+        self
+    }
+
+    pub(crate) fn env_var(&self, name: &str) -> Option<String> {
+        if !self.env_fallback {
+            return None;
+        }
+        match self.env {
+            Some(ref vars) => vars.get(name).cloned(),
+            None => env::var(name).ok()
+        }
+    }
+
+    pub(crate) fn includer_or_default(&self) -> Arc<dyn Includer + Send + Sync> {
+        match self.includer {
+            Some(ref includer) => includer.clone(),
+            None => Arc::new(FileIncluder::new())
+        }
+    }
+
+}
+
+/// Settings for rendering a `Value` as text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    pub(crate) json: bool,
+    pub(crate) indent: usize,
+    pub(crate) compact: bool
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            json: false,
+            indent: 2,
+            compact: false
+        }
+    }
+}
+
+impl RenderOptions {
+
+    pub fn new() -> RenderOptions {
+        RenderOptions::default()
+    }
+
+    /// Writes JSON instead of HOCON. Disabled by default.
+    pub fn json(mut self, enabled: bool) -> RenderOptions {
+        self.json = enabled;
+        self
+    }
+
+    /// Number of spaces per nesting level. Defaults to 2.
+    pub fn indent(mut self, width: usize) -> RenderOptions {
+        self.indent = width;
+        self
+    }
+
+    /// Writes everything on a single line, without optional whitespace.
+    pub fn compact(mut self, enabled: bool) -> RenderOptions {
+        self.compact = enabled;
+        self
+    }
+
+}
+
+/// Settings for turning a `Value` into flat key-value pairs and back.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FlattenOptions {
+    pub(crate) indexed_arrays: bool
+}
+
+impl FlattenOptions {
+
+    pub fn new() -> FlattenOptions {
+        FlattenOptions::default()
+    }
+
+    /// Gives every array element its own entry, under keys like `servers.0`,
+    /// instead of keeping arrays as values. When rebuilding, objects whose keys
+    /// are exactly `0` to `n - 1` become arrays again. Disabled by default.
+    pub fn indexed_arrays(mut self, enabled: bool) -> FlattenOptions {
+        self.indexed_arrays = enabled;
+        self
+    }
+
+}
@@ -0,0 +1,109 @@
+use std::fmt;
+use std::sync::Arc;
+use ::parser::{Field, Node};
+
+/// Where a value was set: the document, the lines of the field and the
+/// comments written just above it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Origin {
+    /// Name of the file or include the document came from, `<string>` otherwise.
+    pub source: Arc<str>,
+    /// First line of the field, starting at 1.
+    pub start_line: usize,
+    /// Last line of the field, the same as `start_line` for single-line fields.
+    pub end_line: usize,
+    /// Lines of the `#` or `//` comments directly above the field, without the comment marker.
+    pub comments: Vec<String>
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.start_line == self.end_line {
+            write!(f, "{}:{}", self.source, self.start_line)
+        } else {
+            write!(f, "{}:{}-{}", self.source, self.start_line, self.end_line)
+        }
+    }
+}
+
+/// Fills in the origin of every field of a document parsed from `input`.
+pub fn locate(node: &mut Node, input: &[u8], source: &str) {
+    let lines = Lines::new(input);
+    locate_node(node, &lines, &Arc::from(source));
+}
+
+fn locate_node(node: &mut Node, lines: &Lines, source: &Arc<str>) {
+    match *node {
+        Node::Object(ref mut fields) => {
+            for field in fields {
+                match *field {
+                    Field::Pair(_, ref mut value, ref mut span) | Field::Append(_, ref mut value, ref mut span) => {
+                        // spans count the input left, so they are offsets from the end of the document
+                        let start = lines.input.len() - span.start;
+                        let end = lines.input.len() - span.end;
+                        let start_line = lines.line_of(start);
+                        span.origin = Some(Arc::new(Origin {
+                            source: source.clone(),
+                            start_line,
+                            end_line: lines.line_of(end.saturating_sub(1).max(start)),
+                            comments: lines.comments_above(start_line)
+                        }));
+                        locate_node(value, lines, source);
+                    },
+                    Field::Include(..) => {}
+                }
+            }
+        },
+        Node::Array(ref mut nodes) | Node::Concatenation(ref mut nodes) => {
+            for node in nodes {
+                locate_node(node, lines, source);
+            }
+        },
+        _ => {}
+    }
+}
+
+struct Lines<'a> {
+    input: &'a [u8],
+    /// Offset where each line starts.
+    starts: Vec<usize>
+}
+
+impl<'a> Lines<'a> {
+
+    fn new(input: &'a [u8]) -> Lines<'a> {
+        let mut starts = vec![0];
+        starts.extend(input.iter().enumerate().filter(|&(_, &c)| c == b'\n').map(|(i, _)| i + 1));
+        Lines { input, starts }
+    }
+
+    /// Line number of the byte at `offset`, starting at 1.
+    fn line_of(&self, offset: usize) -> usize {
+        match self.starts.binary_search(&offset) {
+            Ok(i) => i + 1,
+            Err(i) => i
+        }
+    }
+
+    fn text(&self, line: usize) -> String {
+        let start = self.starts[line - 1];
+        let end = self.starts.get(line).map_or(self.input.len(), |&next| next - 1);
+        String::from_utf8_lossy(&self.input[start..end]).trim().to_string()
+    }
+
+    /// Comment lines right above `line`, stopping at the first line that isn't a comment.
+    fn comments_above(&self, line: usize) -> Vec<String> {
+        let mut comments = Vec::new();
+        for above in (1..line).rev() {
+            let text = self.text(above);
+            let comment = match text.strip_prefix('#').or_else(|| text.strip_prefix("//")) {
+                Some(comment) => comment,
+                None => break
+            };
+            comments.push(comment.trim().to_string());
+        }
+        comments.reverse();
+        comments
+    }
+
+}
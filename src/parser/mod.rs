@@ -1,32 +1,161 @@
 #[cfg(test)] mod tests;
 
-use ::value::Value;
 use nom::*;
-use std::collections::HashMap;
 use std::string::String;
 use std::str;
+use std::sync::Arc;
+use ::origin::Origin;
 
-named!(
-    pub json_value_root<&[u8], Value, u32>,
-    delimited!(
-        json_whitespace,
-        alt!(json_object | json_object_root),
-        json_whitespace
-    )
-);
+/// Parse tree produced by the parser. Unlike `Value`, it can contain
+/// substitutions that are only replaced once the whole document is known.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Node {
+    Null,
+    Boolean(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Node>),
+    /// Fields in source order, not merged yet; a repeated key is resolved
+    /// by `resolve` according to the HOCON merge rules.
+    Object(Vec<Field>),
+    /// Path to look up and whether the substitution is optional (`${?path}`).
+    Substitution(Vec<String>, bool),
+    /// Adjacent values forming a single string, array or object.
+    Concatenation(Vec<Node>),
+    /// Whitespace between the parts of a concatenation, only kept if they
+    /// turn out to be strings.
+    Whitespace(String)
+}
 
-named!(
-    json_value<&[u8], Value>,
-    alt_complete!(
-        json_null |
-        json_boolean |
-        json_float |
-        json_int |
-        json_string |
-        json_array |
-        json_object
-    )
-);
+#[derive(Debug, PartialEq, Clone)]
+pub enum Field {
+    Pair(Vec<String>, Node, Span),
+    /// `path += value`, appending the value to the array at the path.
+    Append(Vec<String>, Node, Span),
+    Include(Include, Span)
+}
+
+/// Where a field was written. The parser only sees the input left to parse,
+/// so it records how much was left at each part of the field; `parse` turns
+/// that into an origin once the whole document is known.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Span {
+    pub start: usize,
+    /// Input left after the key, or after the whole `include` statement.
+    pub key_end: usize,
+    /// Input left at the value, past the separator and the whitespace around it.
+    pub value_start: usize,
+    pub end: usize,
+    pub origin: Option<Arc<Origin>>
+}
+
+/// `include` statement, replaced by the fields of the included document
+/// before substitutions are resolved.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Include {
+    pub kind: IncludeKind,
+    pub name: String,
+    /// Whether a missing document is an error (`include required(...)`).
+    pub required: bool
+}
+
+/// Where an included document should be looked for.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IncludeKind {
+    File,
+    Url,
+    Classpath
+}
+
+// Codes of the `ErrorKind::Custom` errors returned once the parser has read
+// enough to know what comes next, so that the error isn't backtracked from
+// and is reported where the document goes wrong.
+const KEY: u32 = 0;
+const SEPARATOR: u32 = 1;
+const VALUE: u32 = 2;
+const FIELD_END: u32 = 3;
+const ROOT_FIELD_END: u32 = 4;
+const ELEMENT_END: u32 = 5;
+pub const END_OF_INPUT: u32 = 6;
+const QUOTE: u32 = 7;
+const MULTILINE_QUOTE: u32 = 8;
+const SUBSTITUTION_PATH: u32 = 9;
+pub const ESCAPE: u32 = 10;
+
+/// Kinds of tokens the parser expected when it failed with `ErrorKind::Custom(code)`.
+pub fn expected(code: u32) -> Vec<&'static str> {
+    match code {
+        KEY => vec!["key"],
+        SEPARATOR => vec!["':'", "'='", "'+='", "'{'"],
+        VALUE => vec!["value"],
+        FIELD_END => vec!["','", "newline", "'}'"],
+        ROOT_FIELD_END => vec!["','", "newline", "end of input"],
+        ELEMENT_END => vec!["','", "newline", "']'"],
+        END_OF_INPUT => vec!["end of input"],
+        QUOTE => vec!["'\"'"],
+        MULTILINE_QUOTE => vec!["'\"\"\"'"],
+        SUBSTITUTION_PATH => vec!["substitution path"],
+        ESCAPE => vec!["escape sequence"],
+        _ => Vec::new()
+    }
+}
+
+/// Error with one of the codes above, at the input left where it happened.
+fn failure<O>(code: u32, at: &[u8]) -> IResult<&[u8], O> {
+    IResult::Error(error_position!(ErrorKind::Custom(code), at))
+}
+
+/// Whether a parser failed after reading enough to know it was the right one.
+fn committed<O>(result: &IResult<&[u8], O>) -> bool {
+    matches!(*result, IResult::Error(Err::Position(ErrorKind::Custom(_), _)))
+}
+
+/// Whole document: an object with or without braces, followed by nothing
+/// but whitespace and comments.
+pub fn json_value_root(input: &[u8]) -> IResult<&[u8], Node> {
+    let i = skip_whitespace(input);
+    let (rest, root) = if i.starts_with(b"{") {
+        try_parse!(i, json_object)
+    } else {
+        try_parse!(i, json_object_root)
+    };
+    let rest = skip_whitespace(rest);
+    if rest.is_empty() {
+        IResult::Done(rest, root)
+    } else {
+        failure(END_OF_INPUT, rest)
+    }
+}
+
+fn json_value(input: &[u8]) -> IResult<&[u8], Node> {
+    json_concatenation(input)
+}
+
+/// A value that isn't a concatenation. Brackets, braces, quotes and `${`
+/// start a value that has to be complete.
+fn json_single_value(input: &[u8]) -> IResult<&[u8], Node> {
+    if input.starts_with(b"[") {
+        json_array(input)
+    } else if input.starts_with(b"{") {
+        json_object(input)
+    } else if input.starts_with(b"\"") {
+        json_string(input)
+    } else if input.starts_with(b"${") {
+        match json_substitution(input) {
+            IResult::Done(rest, node) => IResult::Done(rest, node),
+            _ => failure(SUBSTITUTION_PATH, &input[2..])
+        }
+    } else {
+        alt_complete!(input,
+            json_null |
+            json_boolean |
+            json_float |
+            json_int |
+            json_string
+        )
+    }
+}
 
 fn json_whitespace(input: &[u8]) -> IResult<&[u8], &[u8]> {
     let len = input.len();
@@ -49,7 +178,7 @@ fn json_whitespace(input: &[u8]) -> IResult<&[u8], &[u8]> {
             break;
         }
     }
-    return IResult::Done(&input[i..], &input[..i]);
+    IResult::Done(&input[i..], &input[..i])
 }
 
 fn inferrable_comma(input: &[u8]) -> IResult<&[u8], &[u8]> {
@@ -82,27 +211,27 @@ fn inferrable_comma(input: &[u8]) -> IResult<&[u8], &[u8]> {
         }
     }
     if got_comma || got_newline {
-        return IResult::Done(&input[i..], &input[..i]);
+        IResult::Done(&input[i..], &input[..i])
     } else {
-        return IResult::Error(error_position!(ErrorKind::Char, &input[i..]));
+        IResult::Error(error_position!(ErrorKind::Char, &input[i..]))
     }
 }
 
 named!(
-    json_null<&[u8], Value>,
-    value!(Value::Null, tag!("null"))
+    json_null<&[u8], Node>,
+    value!(Node::Null, tag!("null"))
 );
 
 named!(
-    json_boolean<&[u8], Value>,
+    json_boolean<&[u8], Node>,
     alt!(
-        tag!("true") => { |_| Value::Boolean(true) } |
-        tag!("false") => { |_| Value::Boolean(false) }
+        tag!("true") => { |_| Node::Boolean(true) } |
+        tag!("false") => { |_| Node::Boolean(false) }
     )
 );
 
 named!(
-    json_int<&[u8], Value>,
+    json_int<&[u8], Node>,
     map!(
         flat_map!(
             recognize!(
@@ -113,26 +242,79 @@ named!(
             ),
             parse_to!(i64)
         ),
-        |i:i64| Value::Int(i)
+        Node::Int
     )
 );
 
 named!(
-    json_float<&[u8], Value>,
+    json_float<&[u8], Node>,
     map!(
         double,
-        |i: f64| { Value::Float(i) }
+        |i: f64| { Node::Float(i) }
     )
 );
 
+fn hex_code_unit(input: &[u8]) -> Option<u32> {
+    if input.len() < 4 || !input[..4].iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    str::from_utf8(&input[..4]).ok().and_then(|hex| u32::from_str_radix(hex, 16).ok())
+}
+
+/// Decodes a `\uXXXX` escape starting at the backslash, combining surrogate
+/// pairs. Returns the character and the length of the escape.
+fn unicode_escape(input: &[u8]) -> Option<(char, usize)> {
+    let first = hex_code_unit(&input[2..])?;
+    match first {
+        0xD800..=0xDBFF => {
+            if input.len() < 8 || &input[6..8] != b"\\u" {
+                return None;
+            }
+            let second = hex_code_unit(&input[8..])?;
+            if !(0xDC00..=0xDFFF).contains(&second) {
+                return None;
+            }
+            let code = 0x10000 + ((first - 0xD800) << 10) + (second - 0xDC00);
+            ::std::char::from_u32(code).map(|c| (c, 12))
+        },
+        0xDC00..=0xDFFF => None,
+        _ => ::std::char::from_u32(first).map(|c| (c, 6))
+    }
+}
+
 fn escaped_string(input: &[u8]) -> IResult<&[u8], Vec<u8>> {
     let len = input.len();
     let mut i = 0;
     let mut s: Vec<u8> = Vec::with_capacity(len);
     while i < len {
         let c = input[i];
-        if c == b'\\' && i < len - 1 && input[i+1] == b'"' {
-            s.push(b'"');
+        if c == b'\\' {
+            if i == len - 1 {
+                return IResult::Incomplete(Needed::Unknown);
+            }
+            let unescaped = match input[i+1] {
+                b'"' => b'"',
+                b'\\' => b'\\',
+                b'/' => b'/',
+                b'b' => b'\x08',
+                b'f' => b'\x0c',
+                b'n' => b'\n',
+                b'r' => b'\r',
+                b't' => b'\t',
+                b'u' => {
+                    match unicode_escape(&input[i..]) {
+                        Some((ch, escape_len)) => {
+                            let mut buf = [0; 4];
+                            s.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+                            i += escape_len;
+                            continue;
+                        },
+                        None => return IResult::Error(error_position!(ErrorKind::Escaped, &input[i..]))
+                    }
+                },
+                _ => return IResult::Error(error_position!(ErrorKind::Escaped, &input[i..]))
+            };
+            s.push(unescaped);
             i += 2;
         } else if c == b'"' {
             return IResult::Done(&input[i..], s);
@@ -144,7 +326,7 @@ fn escaped_string(input: &[u8]) -> IResult<&[u8], Vec<u8>> {
         }
     }
 
-    return IResult::Incomplete(Needed::Unknown);
+    IResult::Incomplete(Needed::Unknown)
 }
 
 named!(
@@ -161,82 +343,156 @@ fn unquoted_string(input: &[u8], allow_dot: bool) -> IResult<&[u8], &[u8]> {
     let mut i = 0;
     while i < len {
         let c = input[i];
-        if c == b'/' && i < len - 1 && input[i+1] == b'/' {
-            break;
-        } else if b"$\"{}[]:=,+#`^?!@*&\\ \t\n\r'".iter().any(|b| b == &c) {
-            break;
-        } else if c == b'.' && !allow_dot {
+        if (c == b'/' && i < len - 1 && input[i+1] == b'/') ||
+            b"$\"{}[]:=,+#`^?!@*&\\ \t\n\r'".iter().any(|b| b == &c) ||
+            (c == b'.' && !allow_dot) {
             break;
         } else {
             i += 1;
         }
     }
     if i > 0 {
-        return IResult::Done(&input[i..], &input[..i]);
+        IResult::Done(&input[i..], &input[..i])
     } else {
-        return IResult::Incomplete(Needed::Size(1));
+        IResult::Incomplete(Needed::Size(1))
     }
 }
 
-named!(
-    json_string<&[u8], Value>,
-    map!(
-        alt_complete!(
-            map!(map_res!(multiline_string, str::from_utf8), String::from) |
-            delimited!(
-                char!('"'),
-                map_res!(escaped_string, String::from_utf8),
-                char!('"')
-            ) |
-            map!(map_res!(apply!(unquoted_string, true), str::from_utf8), String::from)
-        ),
-        |s| Value::String(s)
-    )
-);
+/// Whether `s` can be written without quotes, as a path element if `allow_dot`
+/// is false or as a string value otherwise.
+pub fn is_unquoted(s: &str, allow_dot: bool) -> bool {
+    match unquoted_string(s.as_bytes(), allow_dot) {
+        IResult::Done(rest, _) => rest.is_empty(),
+        _ => false
+    }
+}
+
+/// String between double quotes. Past the opening quote, errors are reported
+/// where the string goes wrong.
+fn quoted_string(input: &[u8]) -> IResult<&[u8], String> {
+    if !input.starts_with(b"\"") {
+        return IResult::Error(error_position!(ErrorKind::Char, input));
+    }
+    match escaped_string(&input[1..]) {
+        IResult::Done(rest, s) => match String::from_utf8(s) {
+            Ok(s) => IResult::Done(&rest[1..], s),
+            Err(_) => IResult::Error(error_position!(ErrorKind::MapRes, input))
+        },
+        IResult::Error(Err::Position(ErrorKind::Escaped, rest)) => failure(ESCAPE, rest),
+        IResult::Error(Err::Position(_, rest)) => failure(QUOTE, rest),
+        _ => failure(QUOTE, &input[input.len()..])
+    }
+}
+
+fn json_string(input: &[u8]) -> IResult<&[u8], Node> {
+    let s = if input.starts_with(b"\"\"\"") {
+        match multiline_string(input) {
+            IResult::Done(rest, s) => match str::from_utf8(s) {
+                Ok(s) => IResult::Done(rest, String::from(s)),
+                Err(_) => IResult::Error(error_position!(ErrorKind::MapRes, input))
+            },
+            _ => return failure(MULTILINE_QUOTE, &input[input.len()..])
+        }
+    } else if input.starts_with(b"\"") {
+        quoted_string(input)
+    } else {
+        map!(input, map_res!(apply!(unquoted_string, true), str::from_utf8), String::from)
+    };
+    s.map(Node::String)
+}
 
 named!(
-    json_array<&[u8], Value>,
+    json_substitution<&[u8], Node>,
     map!(
         delimited!(
-            tuple!(char!('['), json_whitespace),
-            separated_list_complete!(
-                inferrable_comma,
-                json_value
-            ),
-            tuple!(json_whitespace, char!(']'))
+            tag!("${"),
+            pair!(opt!(char!('?')), json_object_path),
+            char!('}')
         ),
-        |elems| Value::Array(elems)
+        |(optional, path): (Option<char>, Vec<String>)| Node::Substitution(path, optional.is_some())
     )
 );
 
-named!(
-    json_object<&[u8], Value>,
-    delimited!(
-        tuple!(char!('{'), json_whitespace),
-        json_object_root,
-        tuple!(json_whitespace, char!('}'))
-    )
-);
+fn concatenation_whitespace(input: &[u8]) -> (&[u8], &[u8]) {
+    let i = input.iter().take_while(|&&c| c == b' ' || c == b'\t').count();
+    (&input[i..], &input[..i])
+}
 
-fn merge_json(
-    old: Value,
-    new: Value
-) -> Value {
-    match (old, new) {
-        (Value::Object(mut obj_prev), Value::Object(mut obj_new)) => {
-            for (key, value) in obj_new.drain() {
-                let new_value = match obj_prev.remove(&key) {
-                    Some(old_value) => merge_json(old_value, value),
-                    _ => value
-                };
-                obj_prev.insert(key, new_value);
+/// Values following each other on the same line. Whitespace between them
+/// is kept, and numbers, booleans and nulls keep the text they were written as,
+/// since they can only be part of a string concatenation.
+fn json_concatenation(input: &[u8]) -> IResult<&[u8], Node> {
+    let mut parts = Vec::new();
+    let mut rest = input;
+    loop {
+        let (start, whitespace) = if parts.is_empty() {
+            (rest, &b""[..])
+        } else {
+            concatenation_whitespace(rest)
+        };
+        match json_single_value(start) {
+            IResult::Done(next, part) => {
+                if !whitespace.is_empty() {
+                    parts.push((Node::Whitespace(String::from_utf8_lossy(whitespace).into_owned()), whitespace));
+                }
+                let text = &start[..start.len() - next.len()];
+                parts.push((part, text));
+                rest = next;
+            },
+            result => {
+                if committed(&result) {
+                    return result;
+                }
+                break;
+            }
+        }
+    }
+    match parts.len() {
+        0 => failure(VALUE, input),
+        1 => IResult::Done(rest, parts.pop().unwrap().0),
+        _ => IResult::Done(rest, Node::Concatenation(parts.into_iter().map(|(part, text)| {
+            match part {
+                Node::Null | Node::Boolean(_) | Node::Int(_) | Node::Float(_) => {
+                    Node::String(String::from_utf8_lossy(text).into_owned())
+                },
+                part => part
+            }
+        }).collect()))
+    }
+}
+
+fn json_array(input: &[u8]) -> IResult<&[u8], Node> {
+    let mut elements = Vec::new();
+    let mut i = skip_whitespace(&input[1..]);
+    while !i.is_empty() && i[0] != b']' {
+        let (rest, element) = try_parse!(i, json_value);
+        elements.push(element);
+        match inferrable_comma(rest) {
+            IResult::Done(next, separator) => {
+                if next.is_empty() || next[0] == b']' {
+                    if let Some(comma) = separator.iter().position(|&c| c == b',') {
+                        return failure(VALUE, &rest[comma..]);
+                    }
+                }
+                i = next;
+            },
+            _ => {
+                i = rest;
+                break;
             }
-            Value::Object(obj_prev)
-        },
-        (_, new) => {
-            new
         }
     }
+    let i = skip_whitespace(i);
+    if i.starts_with(b"]") {
+        IResult::Done(&i[1..], Node::Array(elements))
+    } else {
+        failure(ELEMENT_END, i)
+    }
+}
+
+fn json_object(input: &[u8]) -> IResult<&[u8], Node> {
+    let (rest, object) = try_parse!(&input[1..], apply!(json_fields, Some(b'}')));
+    IResult::Done(&rest[1..], object)
 }
 
 named!(
@@ -244,46 +500,169 @@ named!(
     separated_list_complete!(
         tag!("."),
         alt!(
-            delimited!(
-                char!('"'),
-                map_res!(escaped_string, String::from_utf8),
-                char!('"')
-            ) |
+            quoted_string |
             map!(map_res!(apply!(unquoted_string, false), str::from_utf8), String::from)
         )
     )
 );
 
-named!(
-    json_object_root<&[u8], Value>,
-    map!(
-        separated_list_complete!(
-            inferrable_comma,
-            tuple!(
-                json_object_path,
-                alt!(
-                    preceded!(json_whitespace, json_object) |
-                    preceded!(
-                        tuple!(json_whitespace, alt!(char!(':') | char!('=')), json_whitespace),
-                        json_value
-                    )
-                )
-            )
-        ),
-        |pairs| {
-            let mut obj = Value::Object(HashMap::new());
+/// Step of a path used to look a value up.
+#[derive(Debug, PartialEq, Clone)]
+pub enum PathElement {
+    /// Object key, or array index when written like `servers.0`.
+    Key(String),
+    /// Array index written like `servers[0]`, counted from the end if negative.
+    Index(i64)
+}
 
-            for (path, value) in pairs {
-                let next_pair = path.into_iter().rev().fold(value, |v, key| {
-                    let mut m = HashMap::new();
-                    m.insert(key, v);
-                    Value::Object(m)
-                });
+/// Path used to look a value up: keys separated by dots, each optionally
+/// followed by array indices, like `servers[0].host` or `servers[-1]`.
+pub fn lookup_path(input: &[u8]) -> IResult<&[u8], Vec<PathElement>> {
+    let mut parts = Vec::new();
+    let mut rest = input;
+    while !rest.is_empty() {
+        if rest[0] == b'[' {
+            let end = rest.iter().position(|&c| c == b']').unwrap_or(0);
+            match str::from_utf8(&rest[1..end.max(1)]).ok().and_then(|s| s.parse().ok()) {
+                Some(index) if end > 0 => parts.push(PathElement::Index(index)),
+                _ => return IResult::Error(error_position!(ErrorKind::Digit, rest))
+            }
+            rest = &rest[end + 1..];
+        } else {
+            if !parts.is_empty() {
+                if rest[0] != b'.' {
+                    return IResult::Error(error_position!(ErrorKind::Char, rest));
+                }
+                rest = &rest[1..];
+            }
+            let key: IResult<&[u8], String> = alt_complete!(rest,
+                quoted_string |
+                map!(map_res!(apply!(unquoted_string, false), str::from_utf8), String::from)
+            );
+            match key {
+                IResult::Done(next, key) => {
+                    parts.push(PathElement::Key(key));
+                    rest = next;
+                },
+                _ => return IResult::Error(error_position!(ErrorKind::Alt, rest))
+            }
+        }
+    }
+    IResult::Done(rest, parts)
+}
 
-                obj = merge_json(obj, next_pair);
+named!(
+    include_location<&[u8], (IncludeKind, String)>,
+    alt_complete!(
+        pair!(
+            alt!(
+                tag!("file(") => { |_| IncludeKind::File } |
+                tag!("url(") => { |_| IncludeKind::Url } |
+                tag!("classpath(") => { |_| IncludeKind::Classpath }
+            ),
+            delimited!(json_whitespace, quoted_string, tuple!(json_whitespace, char!(')')))
+        ) |
+        map!(quoted_string, |name: String| {
+            if name.contains("://") {
+                (IncludeKind::Url, name)
+            } else {
+                (IncludeKind::File, name)
             }
+        })
+    )
+);
 
-            obj
-        }
+named!(
+    json_include<&[u8], Include>,
+    preceded!(
+        tuple!(tag!("include"), json_whitespace),
+        alt_complete!(
+            delimited!(
+                tuple!(tag!("required("), json_whitespace),
+                include_location,
+                tuple!(json_whitespace, char!(')'))
+            ) => { |(kind, name)| Include { kind, name, required: true } } |
+            include_location => { |(kind, name)| Include { kind, name, required: false } }
+        )
     )
 );
+
+/// A field along with the span it covers. Past the key, errors are reported
+/// where the field goes wrong.
+fn json_field(input: &[u8]) -> IResult<&[u8], Field> {
+    if let IResult::Done(rest, include) = json_include(input) {
+        let end = rest.len();
+        let span = Span { start: input.len(), key_end: end, value_start: end, end, origin: None };
+        return IResult::Done(rest, Field::Include(include, span));
+    }
+    let (rest, path) = match json_object_path(input) {
+        IResult::Done(rest, path) if !path.is_empty() => (rest, path),
+        _ => return failure(KEY, input)
+    };
+    let i = skip_whitespace(rest);
+    // an object can follow the key without a separator
+    let (append, object, i) = if i.starts_with(b"+=") {
+        (true, false, skip_whitespace(&i[2..]))
+    } else if i.starts_with(b":") || i.starts_with(b"=") {
+        (false, false, skip_whitespace(&i[1..]))
+    } else if i.starts_with(b"{") {
+        (false, true, i)
+    } else {
+        return failure(SEPARATOR, i);
+    };
+    let (next, value) = if object {
+        try_parse!(i, json_object)
+    } else {
+        try_parse!(i, json_value)
+    };
+    let span = Span { start: input.len(), key_end: rest.len(), value_start: i.len(), end: next.len(), origin: None };
+    if append {
+        IResult::Done(next, Field::Append(path, value, span))
+    } else {
+        IResult::Done(next, Field::Pair(path, value, span))
+    }
+}
+
+/// Fields of an object up to the closing brace, which is left in the input,
+/// or up to the end of input for a root object written without braces.
+fn json_fields(input: &[u8], close: Option<u8>) -> IResult<&[u8], Node> {
+    let at_end = |i: &[u8]| i.is_empty() || Some(i[0]) == close;
+    let mut fields = Vec::new();
+    let mut i = skip_whitespace(input);
+    while !at_end(i) {
+        let (rest, field) = try_parse!(i, json_field);
+        fields.push(field);
+        match inferrable_comma(rest) {
+            IResult::Done(next, separator) => {
+                if at_end(next) {
+                    if let Some(comma) = separator.iter().position(|&c| c == b',') {
+                        return failure(KEY, &rest[comma..]);
+                    }
+                }
+                i = next;
+            },
+            _ => {
+                i = rest;
+                break;
+            }
+        }
+    }
+    let i = skip_whitespace(i);
+    match close {
+        Some(c) if i.starts_with(&[c]) => IResult::Done(i, Node::Object(fields)),
+        None if i.is_empty() => IResult::Done(i, Node::Object(fields)),
+        Some(_) => failure(FIELD_END, i),
+        None => failure(ROOT_FIELD_END, i)
+    }
+}
+
+fn json_object_root(input: &[u8]) -> IResult<&[u8], Node> {
+    json_fields(input, None)
+}
+
+pub fn skip_whitespace(input: &[u8]) -> &[u8] {
+    match json_whitespace(input) {
+        IResult::Done(rest, _) => rest,
+        _ => input
+    }
+}
@@ -1,11 +1,40 @@
 use super::*;
-use super::Value::*;
+use ::options::ParseOptions;
+use ::resolve::resolve;
+use ::value::Value;
+use ::value::Value::*;
 use nom::IResult;
+use ::value::Map;
 use std::string::String as Str;
 
+/// Span of a field from the bytes of input left at the start, after the key, at the value and at the end.
+fn span(start: usize, key_end: usize, value_start: usize, end: usize) -> Span {
+    Span { start, key_end, value_start, end, origin: None }
+}
+
+/// Lets parse tests compare parser output with plain values.
+trait Resolved {
+    type Output;
+    fn resolved(self) -> Self::Output;
+}
+
+impl Resolved for Node {
+    type Output = Value;
+    fn resolved(self) -> Value {
+        resolve(&self, &ParseOptions::new().env_fallback(false)).unwrap()
+    }
+}
+
+impl Resolved for Vec<Str> {
+    type Output = Vec<Str>;
+    fn resolved(self) -> Vec<Str> {
+        self
+    }
+}
+
 macro_rules! parse_test(
     ($parser: expr, $input: expr, $output: expr) => (
-        assert_eq!($parser($input.as_bytes()), IResult::Done(&b""[..], $output))
+        assert_eq!($parser($input.as_bytes()).map(Resolved::resolved), IResult::Done(&b""[..], $output))
     )
 );
 
@@ -42,6 +71,62 @@ macro_rules! parse_test(
     parse_test!(json_value, "\"a\\\"b\"", String(Str::from("a\"b")));
 }
 
+#[test] fn test_json_string_escapes() {
+    parse_test!(json_value, r#""a\nb""#, String(Str::from("a\nb")));
+    parse_test!(json_value, r#""C:\\dir""#, String(Str::from("C:\\dir")));
+    parse_test!(json_value, r#""\/\b\f\r\t""#, String(Str::from("/\u{8}\u{c}\r\t")));
+    parse_test!(json_value, r#""\u00e9""#, String(Str::from("\u{e9}")));
+    parse_test!(json_value, r#""\u00E9\u4e2d""#, String(Str::from("\u{e9}\u{4e2d}")));
+    parse_test!(json_value, r#""\ud83d\ude00""#, String(Str::from("\u{1f600}")));
+
+    let invalid = |input: &'static [u8], at: usize| {
+        assert_eq!(
+            escaped_string(input),
+            IResult::Error(Err::Position(ErrorKind::Escaped, &input[at..]))
+        );
+    };
+    invalid(b"a\\qb\"", 1);
+    invalid(b"\\u00g9\"", 0);
+    invalid(b"\\ud83d\"", 0);
+    invalid(b"\\ud83dx\\ude00\"", 0);
+    invalid(b"\\ude00\"", 0);
+
+    assert_eq!(failed_at(br#"a = "ok\n", b = """\q""" # "\q"
+c = "\q""#), Some((37, vec!["escape sequence"])));
+}
+
+/// Offset where the parser fails on a document, with the tokens it expected there.
+fn failed_at(input: &[u8]) -> Option<(usize, Vec<&'static str>)> {
+    match json_value_root(input) {
+        IResult::Error(Err::Position(ErrorKind::Custom(code), rest)) => Some((input.len() - rest.len(), expected(code))),
+        _ => None
+    }
+}
+
+#[test] fn test_errors() {
+    assert_eq!(failed_at(b"a = 1\nb = [1, 2]"), None);
+    assert_eq!(failed_at(b"a b"), Some((2, vec!["':'", "'='", "'+='", "'{'"])));
+    assert_eq!(failed_at(b"a { b = 1, c = }"), Some((15, vec!["value"])));
+    assert_eq!(failed_at(b"a = [1 2 3"), Some((10, vec!["','", "newline", "']'"])));
+    assert_eq!(failed_at(b"a = {b = 1,}"), Some((10, vec!["key"])));
+    assert_eq!(failed_at(b"{ a = 1 } b"), Some((10, vec!["end of input"])));
+    assert_eq!(failed_at(b"a = ${"), Some((6, vec!["substitution path"])));
+    assert_eq!(failed_at(b"a = 1 [2"), Some((8, vec!["','", "newline", "']'"])));
+    assert_eq!(failed_at(b"a = \"\"\"b"), Some((8, vec!["'\"\"\"'"])));
+    assert_eq!(failed_at(b"a = 1 }"), Some((6, vec!["','", "newline", "end of input"])));
+}
+
+#[test] fn test_lookup_path() {
+    use super::PathElement::*;
+    assert_eq!(
+        lookup_path(b"a.\"b.c\"[0][-2].1"),
+        IResult::Done(&b""[..], vec![Key(Str::from("a")), Key(Str::from("b.c")), Index(0), Index(-2), Key(Str::from("1"))])
+    );
+    assert_eq!(lookup_path(b""), IResult::Done(&b""[..], vec![]));
+    assert!(lookup_path(b"a[]").is_err());
+    assert!(lookup_path(b"a.").is_err());
+}
+
 #[test] fn test_json_array() {
     parse_test!(json_value, "[]", Array(vec![]));
     parse_test!(json_value, "[null]", Array(vec![Null]));
@@ -50,14 +135,14 @@ macro_rules! parse_test(
 }
 
 #[test] fn test_json_object() {
-    parse_test!(json_value, "{}", Object(HashMap::new()));
+    parse_test!(json_value, "{}", Object(Map::new()));
     parse_test!(json_value, "{\"a\":42}", Object({
-        let mut m = HashMap::new();
+        let mut m = Map::new();
         m.insert(Str::from("a"), Int(42));
         m
     }));
     parse_test!(json_value, "{\"a\":42,\"b\":43}", Object({
-        let mut m = HashMap::new();
+        let mut m = Map::new();
         m.insert(Str::from("a"), Int(42));
         m.insert(Str::from("b"), Int(43));
         m
@@ -102,7 +187,7 @@ macro_rules! parse_test_eq(
     parse_test!(json_value, "[ 1 , #s\n 2 , 3 ]", Array(vec![Int(1), Int(2), Int(3)]));
     parse_test!(json_value, "[ 1 , #s\n\n 2 , 3 ]", Array(vec![Int(1), Int(2), Int(3)]));
 
-    let m0 = || Object(HashMap::new());
+    let m0 = || Object(Map::new());
     parse_test!(json_value_root, "{}", m0());
     parse_test!(json_value_root, " {} ", m0());
     parse_test!(json_value_root, " { } ", m0());
@@ -111,7 +196,7 @@ macro_rules! parse_test_eq(
     parse_test!(json_value_root, " { \n } ", m0());
 
     let m1 = || {
-        let mut m = HashMap::new();
+        let mut m = Map::new();
         m.insert(Str::from("a"), Int(1));
         Object(m)
     };
@@ -127,7 +212,7 @@ macro_rules! parse_test_eq(
     parse_test!(json_value_root, "\n{\n\"a\"\n:# cmt \n1\n}\n", m1());
 
     let m2 = || {
-        let mut m = HashMap::new();
+        let mut m = Map::new();
         m.insert(Str::from("a"), Int(1));
         m.insert(Str::from("b"), Int(2));
         Object(m)
@@ -148,7 +233,7 @@ macro_rules! parse_test_eq(
     parse_test!(json_value, "[1 , 2 \n# s\n\n, 3]", Array(vec![Int(1), Int(2), Int(3)]));
 
     let m2 = || {
-        let mut m = HashMap::new();
+        let mut m = Map::new();
         m.insert(Str::from("a"), Int(1));
         m.insert(Str::from("b"), Int(2));
         Object(m)
@@ -159,12 +244,12 @@ macro_rules! parse_test_eq(
 
 #[test] fn test_equals_instead_of_colon() {
     parse_test!(json_value, "{\"a\" = 42}", Object({
-        let mut m = HashMap::new();
+        let mut m = Map::new();
         m.insert(Str::from("a"), Int(42));
         m
     }));
     parse_test!(json_value, "{\"a\" = 42,\"b\":43}", Object({
-        let mut m = HashMap::new();
+        let mut m = Map::new();
         m.insert(Str::from("a"), Int(42));
         m.insert(Str::from("b"), Int(43));
         m
@@ -173,16 +258,16 @@ macro_rules! parse_test_eq(
 
 #[test] fn test_skipping_colon_before_object_values() {
     parse_test!(json_value, "{\"a\" = { \"b\":43 }}", Object({
-        let mut m1 = HashMap::new();
+        let mut m1 = Map::new();
         m1.insert(Str::from("b"), Int(43));
-        let mut m2 = HashMap::new();
+        let mut m2 = Map::new();
         m2.insert(Str::from("a"), Object(m1));
         m2
     }));
     parse_test!(json_value, "{\"a\" { \"b\":43 }}", Object({
-        let mut m1 = HashMap::new();
+        let mut m1 = Map::new();
         m1.insert(Str::from("b"), Int(43));
-        let mut m2 = HashMap::new();
+        let mut m2 = Map::new();
         m2.insert(Str::from("a"), Object(m1));
         m2
     }));
@@ -190,23 +275,23 @@ macro_rules! parse_test_eq(
 
 #[test] fn test_dropping_braces_on_root_object() {
     parse_test!(json_value_root, "\"a\" = 42", Object({
-        let mut m = HashMap::new();
+        let mut m = Map::new();
         m.insert(Str::from("a"), Int(42));
         m
     }));
     parse_test!(json_value_root, "\"a\" = 42\n", Object({
-        let mut m = HashMap::new();
+        let mut m = Map::new();
         m.insert(Str::from("a"), Int(42));
         m
     }));
     parse_test!(json_value_root, "\"a\" = 42,\"b\":43", Object({
-        let mut m = HashMap::new();
+        let mut m = Map::new();
         m.insert(Str::from("a"), Int(42));
         m.insert(Str::from("b"), Int(43));
         m
     }));
     parse_test!(json_value_root, "\"a\" = 42\n\"b\":43", Object({
-        let mut m = HashMap::new();
+        let mut m = Map::new();
         m.insert(Str::from("a"), Int(42));
         m.insert(Str::from("b"), Int(43));
         m
@@ -221,10 +306,10 @@ macro_rules! parse_test_eq(
 "a" { "c": 2 }
 "#,
         Object({
-            let mut m1 = HashMap::new();
+            let mut m1 = Map::new();
             m1.insert(Str::from("b"), Int(1));
             m1.insert(Str::from("c"), Int(2));
-            let mut m2 = HashMap::new();
+            let mut m2 = Map::new();
             m2.insert(Str::from("a"), Object(m1));
             m2
         })
@@ -237,12 +322,12 @@ macro_rules! parse_test_eq(
 "a" { "b": { "d": 2 } }
 "#,
         Object({
-            let mut m1 = HashMap::new();
+            let mut m1 = Map::new();
             m1.insert(Str::from("c"), Int(1));
             m1.insert(Str::from("d"), Int(2));
-            let mut m2 = HashMap::new();
+            let mut m2 = Map::new();
             m2.insert(Str::from("b"), Object(m1));
-            let mut m3 = HashMap::new();
+            let mut m3 = Map::new();
             m3.insert(Str::from("a"), Object(m2));
             m3
         })
@@ -255,13 +340,13 @@ macro_rules! parse_test_eq(
 "a" { "b": { "d": 2 } }
 "#,
         Object({
-            let mut m1 = HashMap::new();
+            let mut m1 = Map::new();
             m1.insert(Str::from("c"), Int(1));
             m1.insert(Str::from("d"), Int(2));
-            let mut m2 = HashMap::new();
+            let mut m2 = Map::new();
             m2.insert(Str::from("b"), Object(m1));
             m2.insert(Str::from("e"), Int(3));
-            let mut m3 = HashMap::new();
+            let mut m3 = Map::new();
             m3.insert(Str::from("a"), Object(m2));
             m3
         })
@@ -270,19 +355,19 @@ macro_rules! parse_test_eq(
 
 #[test] fn test_unquoted_strings() {
     parse_test!(json_value, "{a = 42}", Object({
-        let mut m = HashMap::new();
+        let mut m = Map::new();
         m.insert(Str::from("a"), Int(42));
         m
     }));
 
     parse_test!(json_value, "{a = bc}", Object({
-        let mut m = HashMap::new();
+        let mut m = Map::new();
         m.insert(Str::from("a"), String(Str::from("bc")));
         m
     }));
 
     parse_test!(json_value, "{a = b/c}", Object({
-        let mut m = HashMap::new();
+        let mut m = Map::new();
         m.insert(Str::from("a"), String(Str::from("b/c")));
         m
     }));
@@ -294,39 +379,39 @@ macro_rules! parse_test_eq(
     parse_test!(json_object_path, "a", vec![Str::from("a")]);
 
     parse_test!(json_value_root, "a.b = 43", Object({
-        let mut m1 = HashMap::new();
+        let mut m1 = Map::new();
         m1.insert(Str::from("b"), Int(43));
-        let mut m2 = HashMap::new();
+        let mut m2 = Map::new();
         m2.insert(Str::from("a"), Object(m1));
         m2
     }));
 
     parse_test!(json_value_root, "a.b.c = 43", Object({
-        let mut m1 = HashMap::new();
+        let mut m1 = Map::new();
         m1.insert(Str::from("c"), Int(43));
-        let mut m2 = HashMap::new();
+        let mut m2 = Map::new();
         m2.insert(Str::from("b"), Object(m1));
-        let mut m3 = HashMap::new();
+        let mut m3 = Map::new();
         m3.insert(Str::from("a"), Object(m2));
         m3
     }));
 
     parse_test!(json_value_root, "a.\"b\".c = 43", Object({
-        let mut m1 = HashMap::new();
+        let mut m1 = Map::new();
         m1.insert(Str::from("c"), Int(43));
-        let mut m2 = HashMap::new();
+        let mut m2 = Map::new();
         m2.insert(Str::from("b"), Object(m1));
-        let mut m3 = HashMap::new();
+        let mut m3 = Map::new();
         m3.insert(Str::from("a"), Object(m2));
         m3
     }));
 
     parse_test!(json_value_root, "a.\"b.2\".c = 43", Object({
-        let mut m1 = HashMap::new();
+        let mut m1 = Map::new();
         m1.insert(Str::from("c"), Int(43));
-        let mut m2 = HashMap::new();
+        let mut m2 = Map::new();
         m2.insert(Str::from("b.2"), Object(m1));
-        let mut m3 = HashMap::new();
+        let mut m3 = Map::new();
         m3.insert(Str::from("a"), Object(m2));
         m3
     }));
@@ -345,8 +430,83 @@ a = """
 b
 """
 "#, Object({
-    let mut m = HashMap::new();
+    let mut m = Map::new();
     m.insert(Str::from("a"), String(Str::from("\nb\n")));
     m
 }));
 }
+
+#[test] fn test_substitutions() {
+    assert_eq!(
+        json_value(b"${a.b}"),
+        IResult::Done(&b""[..], Node::Substitution(vec![Str::from("a"), Str::from("b")], false))
+    );
+    assert_eq!(
+        json_value(b"\"x\"${a}y"),
+        IResult::Done(&b""[..], Node::Concatenation(vec![
+            Node::String(Str::from("x")),
+            Node::Substitution(vec![Str::from("a")], false),
+            Node::String(Str::from("y"))
+        ]))
+    );
+    assert_eq!(
+        json_value(b"${?a}"),
+        IResult::Done(&b""[..], Node::Substitution(vec![Str::from("a")], true))
+    );
+    parse_test!(json_value_root, "a = 1, b = ${a}", Object({
+        let mut m = Map::new();
+        m.insert(Str::from("a"), Int(1));
+        m.insert(Str::from("b"), Int(1));
+        m
+    }));
+}
+
+#[test] fn test_includes() {
+    let include = |kind, name: &str, required, start, end| {
+        Field::Include(Include { kind, name: Str::from(name), required }, span(start, end, end, end))
+    };
+    assert_eq!(
+        json_object_root(b"include \"a.conf\"\ninclude file(\"b.conf\")\ninclude required( file(\"c.conf\") )"),
+        IResult::Done(&b""[..], Node::Object(vec![
+            include(IncludeKind::File, "a.conf", false, 74, 58),
+            include(IncludeKind::File, "b.conf", false, 57, 35),
+            include(IncludeKind::File, "c.conf", true, 34, 0)
+        ]))
+    );
+    assert_eq!(
+        json_object_root(b"include url(\"http://a/b.conf\"), include classpath(\"c.conf\"), include \"http://d.conf\""),
+        IResult::Done(&b""[..], Node::Object(vec![
+            include(IncludeKind::Url, "http://a/b.conf", false, 84, 54),
+            include(IncludeKind::Classpath, "c.conf", false, 52, 25),
+            include(IncludeKind::Url, "http://d.conf", false, 23, 0)
+        ]))
+    );
+    assert_eq!(
+        json_object_root(b"include = 1"),
+        IResult::Done(&b""[..], Node::Object(vec![Field::Pair(vec![Str::from("include")], Node::Int(1), span(11, 4, 1, 0))]))
+    );
+}
+
+#[test] fn test_append() {
+    assert_eq!(
+        json_object_root(b"a.b += 1"),
+        IResult::Done(&b""[..], Node::Object(vec![
+            Field::Append(vec![Str::from("a"), Str::from("b")], Node::Int(1), span(8, 5, 1, 0))
+        ]))
+    );
+}
+
+#[test] fn test_concatenation() {
+    assert_eq!(
+        json_value(b"a 1.0  [b]"),
+        IResult::Done(&b""[..], Node::Concatenation(vec![
+            Node::String(Str::from("a")),
+            Node::Whitespace(Str::from(" ")),
+            Node::String(Str::from("1.0")),
+            Node::Whitespace(Str::from("  ")),
+            Node::Array(vec![Node::String(Str::from("b"))])
+        ]))
+    );
+    assert_eq!(json_value(b"a \n"), IResult::Done(&b" \n"[..], Node::String(Str::from("a"))));
+    assert_eq!(json_value(b"1 ,"), IResult::Done(&b" ,"[..], Node::Int(1)));
+}
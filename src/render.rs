@@ -0,0 +1,173 @@
+use std::fmt::Write;
+use ::options::RenderOptions;
+use ::parser::is_unquoted;
+use ::value::{Map, Value};
+
+impl Value {
+
+    /// Renders the value as HOCON or JSON text that parses back to an equal value.
+    /// Keys are written in the object's order, and only quoted when they have to be.
+    /// Floats that are not finite can't be written and become `null`.
+    /// A document is always an object, so other values don't parse back as one;
+    /// rendered as JSON, they are still valid for other JSON parsers.
+    pub fn render(&self, options: &RenderOptions) -> String {
+        let renderer = Renderer { options };
+        let mut out = String::new();
+        match *self {
+            // a HOCON document is an object without braces around it, unless it has no fields
+            Value::Object(ref obj) if !options.json && !obj.is_empty() => renderer.fields(obj, 0, &mut out),
+            _ => renderer.value(self, 0, &mut out)
+        }
+        out
+    }
+
+}
+
+/// Renders a value the way it is written after a key, so objects keep their braces.
+pub(crate) fn render_value(value: &Value, options: &RenderOptions) -> String {
+    let mut out = String::new();
+    Renderer { options }.value(value, 0, &mut out);
+    out
+}
+
+/// Writes a path of keys separated by dots, quoting the keys that need it.
+pub(crate) fn render_path(path: &[String]) -> String {
+    let mut out = String::new();
+    for (i, key) in path.iter().enumerate() {
+        if i > 0 {
+            out.push('.');
+        }
+        if is_unquoted(key, false) {
+            out.push_str(key);
+        } else {
+            quote(key, &mut out);
+        }
+    }
+    out
+}
+
+struct Renderer<'a> {
+    options: &'a RenderOptions
+}
+
+impl<'a> Renderer<'a> {
+
+    fn newline(&self, level: usize, out: &mut String) {
+        out.push('\n');
+        out.extend((0..level * self.options.indent).map(|_| ' '));
+    }
+
+    /// Whether elements and fields are separated by commas rather than newlines.
+    fn commas(&self) -> bool {
+        self.options.json || self.options.compact
+    }
+
+    fn value(&self, value: &Value, level: usize, out: &mut String) {
+        match *value {
+            Value::Null => out.push_str("null"),
+            Value::Boolean(b) => out.push_str(if b { "true" } else { "false" }),
+            Value::Int(i) => out.push_str(&i.to_string()),
+            Value::Float(f) if f.is_finite() => out.push_str(&float(f)),
+            Value::Float(_) => out.push_str("null"),
+            Value::String(ref s) => self.string(s, out),
+            Value::Array(ref elems) if elems.is_empty() => out.push_str("[]"),
+            Value::Array(ref elems) => {
+                out.push('[');
+                for (i, elem) in elems.iter().enumerate() {
+                    if i > 0 && self.commas() {
+                        out.push(',');
+                    }
+                    if !self.options.compact {
+                        self.newline(level + 1, out);
+                    }
+                    self.value(elem, level + 1, out);
+                }
+                if !self.options.compact {
+                    self.newline(level, out);
+                }
+                out.push(']');
+            },
+            Value::Object(ref obj) if obj.is_empty() => out.push_str("{}"),
+            Value::Object(ref obj) => {
+                out.push('{');
+                self.fields(obj, level + 1, out);
+                if !self.options.compact {
+                    self.newline(level, out);
+                }
+                out.push('}');
+            }
+        }
+    }
+
+    fn fields(&self, obj: &Map, level: usize, out: &mut String) {
+        for (i, (key, value)) in obj.iter().enumerate() {
+            if i > 0 && self.commas() {
+                out.push(',');
+            }
+            if !self.options.compact && (i > 0 || level > 0) {
+                self.newline(level, out);
+            }
+            if !self.options.json && is_unquoted(key, false) {
+                out.push_str(key);
+            } else {
+                quote(key, out);
+            }
+            match (self.options.json, self.options.compact, value) {
+                (true, true, _) => out.push(':'),
+                (true, false, _) => out.push_str(": "),
+                (false, true, &Value::Object(_)) => {},
+                (false, false, &Value::Object(_)) => out.push(' '),
+                (false, true, _) => out.push('='),
+                (false, false, _) => out.push_str(" = ")
+            }
+            self.value(value, level, out);
+        }
+    }
+
+    /// Multi-line strings are written between `"""` when their content can't end the string early.
+    fn string(&self, s: &str, out: &mut String) {
+        let multiline = !self.options.json && !self.options.compact && s.contains('\n') &&
+            !s.contains("\"\"\"") && !s.ends_with('"');
+        if multiline {
+            out.push_str("\"\"\"");
+            out.push_str(s);
+            out.push_str("\"\"\"");
+        } else {
+            quote(s, out);
+        }
+    }
+
+}
+
+fn quote(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if c < ' ' => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            },
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+}
+
+/// Formats a float so that it reads back as one: the mantissa always has a
+/// decimal point, as `1e16` alone would be read as a string.
+fn float(f: f64) -> String {
+    let s = format!("{:?}", f);
+    if s.contains('.') {
+        return s;
+    }
+    match s.find('e') {
+        Some(e) => format!("{}.0{}", &s[..e], &s[e..]),
+        None => format!("{}.0", s)
+    }
+}
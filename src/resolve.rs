@@ -0,0 +1,440 @@
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use ::error::Error;
+use ::options::ParseOptions;
+use ::origin::Origin;
+use ::parser::{Field, Node};
+use ::value::{Map, Value};
+
+/// Turns a parse tree into a `Value`: merges repeated keys and replaces
+/// every substitution with the value found at its path in the root object,
+/// or in the environment if the options allow it.
+pub fn resolve(root: &Node, options: &ParseOptions) -> Result<Value, Error> {
+    let mut assignments = Vec::new();
+    flatten(&[], root, None, &mut assignments);
+    let mut index = PathIndex::default();
+    for (i, assignment) in assignments.iter_mut().enumerate() {
+        assignment.index = Some(i);
+        index.insert(&assignment.path, i);
+    }
+    let mut resolver = Resolver {
+        assignments: &assignments,
+        index,
+        options,
+        fields: Vec::new(),
+        stack: Vec::new(),
+        substituted: HashMap::new()
+    };
+    let all: Vec<&Assignment> = assignments.iter().collect();
+    resolver.value_of(&[], &all).map(|v| v.unwrap_or_else(|| Value::Object(Map::new())))
+}
+
+/// Single `path = value` assignment, with object literals broken down into
+/// one assignment per field. The order of assignments is the source order.
+struct Assignment<'a> {
+    path: Vec<String>,
+    source: Source<'a>,
+    /// Position among the root document's assignments, used to find what
+    /// a self-referential substitution saw before its own assignment.
+    index: Option<usize>,
+    /// Where the field was written, kept for the key it sets.
+    origin: Option<Arc<Origin>>
+}
+
+enum Source<'a> {
+    /// Marks that the path is set to an object; its fields are separate assignments.
+    Object,
+    Node(&'a Node),
+    /// Value appended to the array the path held before.
+    Append(&'a Node),
+    /// Already known value, used when a parent's value acts as a fallback for its children.
+    Value(Value)
+}
+
+/// Value along with where it was set.
+type Located = (Value, Option<Arc<Origin>>);
+
+/// Outcome of looking a path up inside a value.
+enum Walk<'v> {
+    /// The value, with its origin in the object holding it.
+    Found(&'v Value, Option<Arc<Origin>>),
+    /// An object on the way didn't have the key, so older assignments may still set it.
+    Missing,
+    /// A non-object value on the way hides anything assigned before it.
+    Blocked
+}
+
+fn flatten<'a>(prefix: &[String], node: &'a Node, origin: Option<Arc<Origin>>, out: &mut Vec<Assignment<'a>>) {
+    match *node {
+        Node::Object(ref fields) => {
+            out.push(Assignment { path: prefix.to_vec(), source: Source::Object, index: None, origin });
+            for field in fields {
+                match *field {
+                    Field::Pair(ref path, ref value, ref span) => {
+                        let mut full_path = prefix.to_vec();
+                        full_path.extend(path.iter().cloned());
+                        flatten(&full_path, value, span.origin.clone(), out);
+                    },
+                    Field::Append(ref path, ref value, ref span) => {
+                        let mut full_path = prefix.to_vec();
+                        full_path.extend(path.iter().cloned());
+                        let origin = span.origin.clone();
+                        out.push(Assignment { path: full_path, source: Source::Append(value), index: None, origin });
+                    },
+                    // includes have been replaced by the included fields before resolving
+                    Field::Include(..) => {}
+                }
+            }
+        },
+        _ => {
+            out.push(Assignment { path: prefix.to_vec(), source: Source::Node(node), index: None, origin });
+        }
+    }
+}
+
+fn walk<'v>(value: &'v Value, path: &[String]) -> Walk<'v> {
+    let mut current = value;
+    let mut origin = None;
+    for key in path {
+        match *current {
+            Value::Object(ref obj) => {
+                match obj.get(key) {
+                    Some(v) => {
+                        current = v;
+                        origin = obj.origin_arc(key);
+                    },
+                    None => return Walk::Missing
+                }
+            },
+            _ => return Walk::Blocked
+        }
+    }
+    Walk::Found(current, origin)
+}
+
+/// Merges two values the way a repeated key does: objects are merged
+/// recursively, anything else in `new` replaces `old`. Keys of `old` keep
+/// their position, and keys only in `new` come after them. Each key keeps
+/// the origin of the side its value came from.
+pub(crate) fn merge(new: Value, old: Value) -> Value {
+    match (new, old) {
+        (Value::Object(mut new_obj), Value::Object(old_obj)) => {
+            let mut merged = Map::new();
+            for (key, old_value, old_origin) in old_obj.into_entries() {
+                match new_obj.shift_remove_with_origin(&key) {
+                    Some((new_value, new_origin)) => {
+                        merged.insert_with_origin(key, merge(new_value, old_value), new_origin);
+                    },
+                    None => merged.insert_with_origin(key, old_value, old_origin)
+                }
+            }
+            for (key, value, origin) in new_obj.into_entries() {
+                merged.insert_with_origin(key, value, origin);
+            }
+            Value::Object(merged)
+        },
+        (new, _) => new
+    }
+}
+
+/// What a value can be concatenated with: arrays only with arrays, objects
+/// only with objects, and anything else becomes part of a string.
+fn concatenation_kind(value: &Value) -> &'static str {
+    match *value {
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+        _ => "string"
+    }
+}
+
+/// Positions of the root document's assignments, arranged by their paths,
+/// to find those a substitution depends on without going through all of them.
+#[derive(Default)]
+struct PathIndex {
+    /// Assignments to the path of this node.
+    here: Vec<usize>,
+    children: HashMap<String, PathIndex>
+}
+
+impl PathIndex {
+
+    fn insert(&mut self, path: &[String], position: usize) {
+        match path.split_first() {
+            None => self.here.push(position),
+            Some((key, rest)) => self.children.entry(key.clone()).or_default().insert(rest, position)
+        }
+    }
+
+    /// Positions of the assignments to `path`, to one of its parents or to a
+    /// path inside it, in source order.
+    fn related(&self, path: &[String]) -> Vec<usize> {
+        let mut positions = Vec::new();
+        let mut node = Some(self);
+        for key in path {
+            node = node.and_then(|n| {
+                positions.extend_from_slice(&n.here);
+                n.children.get(key)
+            });
+        }
+        if let Some(node) = node {
+            node.all(&mut positions);
+        }
+        positions.sort_unstable();
+        positions
+    }
+
+    fn all(&self, out: &mut Vec<usize>) {
+        out.extend_from_slice(&self.here);
+        for child in self.children.values() {
+            child.all(out);
+        }
+    }
+
+}
+
+struct Resolver<'a> {
+    assignments: &'a [Assignment<'a>],
+    index: PathIndex,
+    options: &'a ParseOptions,
+    /// Path and position of the root assignments whose values are being computed.
+    fields: Vec<(Vec<String>, usize)>,
+    /// Substitutions currently being looked up, each with the position of the
+    /// first assignment it may not see, used to detect cycles.
+    stack: Vec<(Vec<String>, Option<usize>)>,
+    /// Values of the substitutions looked up so far, by path and position
+    /// like on the stack. Any error ends resolving, so only values are kept.
+    substituted: HashMap<(Vec<String>, Option<usize>), Option<Value>>
+}
+
+impl<'a> Resolver<'a> {
+
+    /// Computes the value at `path` from the assignments that touch it,
+    /// given in source order. Returns `None` if the path ends up undefined.
+    fn value_of(&mut self, path: &[String], assignments: &[&Assignment]) -> Result<Option<Value>, Error> {
+        Ok(self.located_value_of(path, assignments)?.map(|(value, _)| value))
+    }
+
+    /// Like `value_of`, along with the origin of the assignment whose value won.
+    fn located_value_of(&mut self, path: &[String], assignments: &[&Assignment]) -> Result<Option<Located>, Error> {
+        // the newest assignment to the path itself or to one of its parents
+        let whole = assignments.iter().rposition(|a| a.path.len() <= path.len());
+        let (base, newer) = match whole {
+            None => (None, assignments),
+            Some(w) => {
+                let assignment = assignments[w];
+                let older = &assignments[..w];
+                let base = match self.evaluate(assignment)? {
+                    None => {
+                        // an undefined optional substitution leaves the field as it was
+                        let mut rest = older.to_vec();
+                        rest.extend_from_slice(&assignments[w + 1..]);
+                        return self.located_value_of(path, &rest);
+                    },
+                    Some(value) => {
+                        match walk(&value, &path[assignment.path.len()..]) {
+                            Walk::Found(found, origin) => {
+                                // a value found inside the assigned one keeps its own origin
+                                let origin = origin.or_else(|| assignment.origin.clone());
+                                match (found, self.value_of(path, older)?) {
+                                    (Value::Object(obj), Some(fallback)) => {
+                                        Some((merge(Value::Object(obj.clone()), fallback), origin))
+                                    },
+                                    (found, _) => Some((found.clone(), origin))
+                                }
+                            },
+                            Walk::Missing => self.located_value_of(path, older)?,
+                            Walk::Blocked => None
+                        }
+                    }
+                };
+                (base, &assignments[w + 1..])
+            }
+        };
+
+        if newer.is_empty() {
+            return Ok(base);
+        }
+
+        // fields set after the newest whole assignment are merged on top of it,
+        // and the object keeps the origin of that assignment if there is one
+        let (mut obj, origin) = match base {
+            Some((Value::Object(obj), origin)) => (obj, origin),
+            _ => (Map::new(), None)
+        };
+        let mut children: IndexMap<&String, Vec<&Assignment>> = IndexMap::new();
+        for &assignment in newer {
+            children.entry(&assignment.path[path.len()]).or_default().push(assignment);
+        }
+        for (key, assignments) in children {
+            let mut child_path = path.to_vec();
+            child_path.push(key.clone());
+            let fallback = obj.get(key).cloned().map(|v| {
+                Assignment { path: child_path.clone(), source: Source::Value(v), index: None, origin: obj.origin_arc(key) }
+            });
+            let mut child: Vec<&Assignment> = fallback.iter().collect();
+            child.extend(assignments);
+            // inserting a key that is already there keeps its position
+            match self.located_value_of(&child_path, &child)? {
+                Some((value, origin)) => obj.insert_with_origin(key.clone(), value, origin),
+                None => {
+                    obj.shift_remove(key);
+                }
+            }
+        }
+        let origin = origin.or_else(|| newer.iter().rev().find_map(|a| a.origin.clone()));
+        Ok(Some((Value::Object(obj), origin)))
+    }
+
+    fn evaluate(&mut self, assignment: &Assignment) -> Result<Option<Value>, Error> {
+        if let Some(index) = assignment.index {
+            self.fields.push((assignment.path.clone(), index));
+        }
+        let result = match assignment.source {
+            Source::Object => Ok(Some(Value::Object(Map::new()))),
+            Source::Node(node) => self.resolve(node),
+            Source::Append(node) => self.append(assignment, node),
+            Source::Value(ref value) => Ok(Some(value.clone()))
+        };
+        if assignment.index.is_some() {
+            self.fields.pop();
+        }
+        result
+    }
+
+    /// `path += node` is the same as `path = ${?path} [node]`.
+    fn append(&mut self, assignment: &Assignment, node: &Node) -> Result<Option<Value>, Error> {
+        let mut elems = match self.substitute(&assignment.path)? {
+            None => Vec::new(),
+            Some(Value::Array(elems)) => elems,
+            Some(_) => {
+                let err = Error::AtPath(assignment.path.join("."), Box::new(Error::IncompatibleType));
+                return Err(match assignment.origin {
+                    Some(ref origin) => Error::AtOrigin(Box::new(err), (**origin).clone()),
+                    None => err
+                });
+            }
+        };
+        if let Some(value) = self.resolve(node)? {
+            elems.push(value);
+        }
+        Ok(Some(Value::Array(elems)))
+    }
+
+    fn resolve(&mut self, node: &Node) -> Result<Option<Value>, Error> {
+        match *node {
+            Node::Null => Ok(Some(Value::Null)),
+            Node::Boolean(b) => Ok(Some(Value::Boolean(b))),
+            Node::Int(i) => Ok(Some(Value::Int(i))),
+            Node::Float(f) => Ok(Some(Value::Float(f))),
+            Node::String(ref s) => Ok(Some(Value::String(s.clone()))),
+            Node::Array(ref elems) => {
+                let mut arr = Vec::with_capacity(elems.len());
+                for elem in elems {
+                    // undefined optional substitutions are left out of arrays
+                    if let Some(value) = self.resolve(elem)? {
+                        arr.push(value);
+                    }
+                }
+                Ok(Some(Value::Array(arr)))
+            },
+            Node::Object(_) => {
+                let mut assignments = Vec::new();
+                flatten(&[], node, None, &mut assignments);
+                let all: Vec<&Assignment> = assignments.iter().collect();
+                self.value_of(&[], &all)
+            },
+            Node::Substitution(ref path, optional) => {
+                match self.substitute(path)? {
+                    None if !optional => Err(Error::UnresolvedSubstitution(path.join("."))),
+                    value => Ok(value)
+                }
+            },
+            Node::Concatenation(ref parts) => self.concatenate(parts),
+            Node::Whitespace(ref s) => Ok(Some(Value::String(s.clone())))
+        }
+    }
+
+    /// Joins strings, concatenates arrays or merges objects. Parts that are
+    /// undefined optional substitutions are left out.
+    fn concatenate(&mut self, parts: &[Node]) -> Result<Option<Value>, Error> {
+        let mut values = Vec::with_capacity(parts.len());
+        for part in parts {
+            values.push(match *part {
+                Node::Whitespace(_) => None,
+                _ => self.resolve(part)?
+            });
+        }
+        let kind = match values.iter().flatten().next() {
+            Some(first) => concatenation_kind(first),
+            None => return Ok(None)
+        };
+        for value in values.iter().flatten() {
+            if concatenation_kind(value) != kind {
+                return Err(Error::ConcatenationMismatch(kind, concatenation_kind(value)));
+            }
+        }
+        Ok(Some(match kind {
+            "array" => {
+                let mut arr = Vec::new();
+                for value in values.into_iter().flatten() {
+                    if let Value::Array(mut elems) = value {
+                        arr.append(&mut elems);
+                    }
+                }
+                Value::Array(arr)
+            },
+            "object" => {
+                values.into_iter().flatten().fold(Value::Object(Map::new()), |acc, value| merge(value, acc))
+            },
+            _ => {
+                let mut s = String::new();
+                for (part, value) in parts.iter().zip(values) {
+                    match (part, value) {
+                        (Node::Whitespace(ws), _) => s.push_str(ws),
+                        (_, Some(Value::Null)) => s.push_str("null"),
+                        (_, Some(Value::Boolean(b))) => s.push_str(&b.to_string()),
+                        (_, Some(Value::Int(i))) => s.push_str(&i.to_string()),
+                        (_, Some(Value::Float(f))) => s.push_str(&f.to_string()),
+                        (_, Some(Value::String(ref p))) => s.push_str(p),
+                        _ => {}
+                    }
+                }
+                Value::String(s)
+            }
+        }))
+    }
+
+    fn substitute(&mut self, path: &[String]) -> Result<Option<Value>, Error> {
+        // a substitution pointing at its own field, or inside it, refers to
+        // the value the field had before this assignment
+        let limit = match self.fields.last() {
+            Some(&(ref field, index)) if path.starts_with(field) => Some(index),
+            _ => None
+        };
+        let entry = (path.to_vec(), limit);
+        if let Some(value) = self.substituted.get(&entry) {
+            return Ok(value.clone());
+        }
+        if let Some(start) = self.stack.iter().position(|e| e == &entry) {
+            let mut cycle: Vec<String> = self.stack[start..].iter().map(|e| e.0.join(".")).collect();
+            cycle.push(path.join("."));
+            return Err(Error::SubstitutionCycle(cycle));
+        }
+        self.stack.push(entry.clone());
+        let all = self.assignments;
+        let assignments: Vec<&Assignment> = self.index.related(path).into_iter()
+            .filter(|&i| limit.is_none() || Some(i) < limit)
+            .map(|i| &all[i])
+            .collect();
+        let result = self.value_of(path, &assignments);
+        self.stack.pop();
+        let value = match result? {
+            None => self.options.env_var(&path.join(".")).map(Value::String),
+            value => value
+        };
+        self.substituted.insert(entry, value.clone());
+        Ok(value)
+    }
+
+}
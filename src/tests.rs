@@ -1,28 +1,108 @@
-use super::from_str;
+use super::{from_file, from_str, from_str_with_options};
+use ::include::{FileIncluder, Included, Includer};
+use ::parser::IncludeKind;
+use ::options::{FlattenOptions, ParseOptions, RenderOptions};
+use ::loader::ConfigLoader;
+use ::document::Document;
+use ::config::Config;
 use ::value::Value;
 use ::error::Error;
+use ::value::Map;
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
 use std::string::String;
 
 #[test] fn test_full_parse() {
     assert_eq!(
         from_str("a = 2"),
-        Ok(Value::Object({
-            let mut m = HashMap::new();
-            m.insert(String::from("a"), Value::Int(2));
-            m
-        }))
+        Ok(hocon!({ a: 2 }))
     );
+    assert_eq!(from_str(""), Ok(Value::Object(Map::new())));
+    assert_eq!(from_str("# nothing here\n  // yet\n"), Ok(Value::Object(Map::new())));
 }
 
 #[test] fn test_incomplete_parse() {
-    assert_eq!(
-        from_str("a ="),
-        Err(Error::ExtraInput(0))
-    );
+    match from_str("a =") {
+        Err(Error::ParseError(at)) => {
+            assert_eq!((at.offset, at.line, at.column), (3, 1, 4));
+            assert_eq!(at.expected, vec!["value"]);
+        },
+        other => panic!("unexpected result {:?}", other)
+    }
+    match from_str("{ a = 1 }\n}") {
+        Err(Error::ExtraInput(at)) => {
+            assert_eq!((at.offset, at.line, at.column), (10, 2, 1));
+            assert_eq!(at.expected, vec!["end of input"]);
+        },
+        other => panic!("unexpected result {:?}", other)
+    }
+    // input ending inside an object
+    for &(input, offset) in &[("{", 1), ("{ a = 1", 7)] {
+        match from_str(input) {
+            Err(Error::ParseError(at)) => {
+                assert_eq!((at.offset, at.line, at.column), (offset, 1, offset + 1));
+                assert_eq!(at.expected, vec!["','", "newline", "'}'"]);
+            },
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+}
+
+#[test] fn test_parse_error_positions() {
+    let err = from_str("a = 1\nb { c d }").unwrap_err();
+    assert_eq!(err.to_string(), "error: expected one of ':', '=', '+=' or '{'
+ --> <string>:2:7
+  |
+2 | b { c d }
+  |       ^");
+
+    let err = from_str("a = [1, 2,]").unwrap_err();
+    assert_eq!(err.to_string(), "error: expected value
+ --> <string>:1:10
+  |
+1 | a = [1, 2,]
+  |          ^");
+
+    match from_str("k\u{e9} = \"\u{e9}t\u{e9}\n") {
+        Err(Error::ParseError(at)) => {
+            assert_eq!((at.offset, at.line, at.column), (12, 1, 10));
+            assert_eq!(at.expected, vec!["'\"'"]);
+        },
+        other => panic!("unexpected result {:?}", other)
+    }
+
+    let dir = write_files("parse-error", &[("main.conf", "include \"bad.conf\""), ("bad.conf", "a = 1\n\nb = ")]);
+    match from_file(dir.join("main.conf")) {
+        Err(Error::ParseError(at)) => {
+            assert_eq!(at.origin, dir.join("bad.conf").display().to_string());
+            assert_eq!((at.line, at.column), (3, 5));
+        },
+        other => panic!("unexpected result {:?}", other)
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test] fn test_invalid_escape() {
+    let err = from_str("a = 1\nb = \"x\\q\"").unwrap_err();
+    match err {
+        Error::InvalidEscape(ref at) => assert_eq!((at.offset, at.line, at.column), (12, 2, 7)),
+        ref other => panic!("unexpected error {:?}", other)
+    }
+    assert_eq!(err.to_string(), "error: invalid escape sequence
+ --> <string>:2:7
+  |
+2 | b = \"x\\q\"
+  |       ^");
+    assert_eq!(from_str("a = \"\\u00e9\"").unwrap().get_string("a"), Ok(String::from("\u{e9}")));
 }
 
-#[test] fn test_value_extraction() {
+#[test]
+#[allow(clippy::bool_assert_comparison)]
+fn test_value_extraction() {
     assert_eq!(from_str("a = 42").unwrap().get("a"), Ok(Value::Int(42)));
     assert_eq!(from_str("a.b = 42").unwrap().get("a.b"), Ok(Value::Int(42)));
     assert_eq!(from_str("a.b.c = 42").unwrap().get("a.b.c"), Ok(Value::Int(42)));
@@ -49,3 +129,938 @@ use std::string::String;
     assert_eq!(from_str("a = \"true\"").unwrap().get_string("a"), Ok(String::from("true")));
 
 }
+
+#[test] fn test_substitutions() {
+    assert_eq!(from_str("a = 1, b = ${a}").unwrap().get("b"), Ok(Value::Int(1)));
+    assert_eq!(from_str("b = ${a.c}, a { c = x }").unwrap().get("b"), Ok(Value::String(String::from("x"))));
+    assert_eq!(from_str("a = [1, 2], b = ${a}").unwrap().get("b"), Ok(Value::Array(vec![Value::Int(1), Value::Int(2)])));
+    assert_eq!(from_str("a { b = 1 }, c = ${a}, d = ${c.b}").unwrap().get("d"), Ok(Value::Int(1)));
+    assert_eq!(
+        from_str(r#"
+db.host = localhost
+db.port = 5432
+db.url = "jdbc://"${db.host}":"${db.port}
+"#).unwrap().get_string("db.url"),
+        Ok(String::from("jdbc://localhost:5432"))
+    );
+    assert_eq!(from_str("a = ${b}"), Err(Error::UnresolvedSubstitution(String::from("b"))));
+    assert_eq!(from_str("a { c = 1 }, b = ${a.d}"), Err(Error::UnresolvedSubstitution(String::from("a.d"))));
+    assert_eq!(from_str("a = ${b}").unwrap_err().to_string(), "substitution ${b} could not be resolved");
+}
+
+#[test] fn test_optional_substitutions() {
+    assert_eq!(from_str("a = ${?b}").unwrap().get("a"), Err(Error::NotFound(String::from("a"))));
+    assert_eq!(from_str("a = 1, a = ${?b}").unwrap().get("a"), Ok(Value::Int(1)));
+    assert_eq!(from_str("a = 1, a = ${?b}, b = 2").unwrap().get("a"), Ok(Value::Int(2)));
+    assert_eq!(from_str("a.c = 1, a = ${?b}").unwrap().get("a.c"), Ok(Value::Int(1)));
+    assert_eq!(from_str("a = [1, ${?b}, 2]").unwrap().get("a"), Ok(Value::Array(vec![Value::Int(1), Value::Int(2)])));
+    assert_eq!(from_str("a = x${?b}y").unwrap().get("a"), Ok(Value::String(String::from("xy"))));
+    assert_eq!(from_str("a = ${?b}${?c}").unwrap().get("a"), Err(Error::NotFound(String::from("a"))));
+}
+
+#[test] fn test_object_merging_with_substitutions() {
+    assert_eq!(from_str("a = 5, a.b = 1").unwrap().get("a.b"), Ok(Value::Int(1)));
+    assert_eq!(from_str("a { b = 1 }, a = 5").unwrap().get("a"), Ok(Value::Int(5)));
+    assert_eq!(from_str("x { c = 2 }, a { b = 1 }, a = ${x}").unwrap().get("a.b"), Ok(Value::Int(1)));
+    assert_eq!(from_str("x { c = 2 }, a { b = 1 }, a = ${x}").unwrap().get("a.c"), Ok(Value::Int(2)));
+    assert_eq!(from_str("x = 2, a { b = 1 }, a = ${x}").unwrap().get("a"), Ok(Value::Int(2)));
+    assert_eq!(from_str("a = ${x}, a { b = 1 }, x { c = 2 }").unwrap().get("a.c"), Ok(Value::Int(2)));
+}
+
+#[test] fn test_self_referential_substitutions() {
+    assert_eq!(
+        from_str("path = \"/bin\", path = ${path}\":/opt/bin\"").unwrap().get_string("path"),
+        Ok(String::from("/bin:/opt/bin"))
+    );
+    assert_eq!(from_str("a = x, a = ${a}y, a = ${a}z").unwrap().get_string("a"), Ok(String::from("xyz")));
+    assert_eq!(from_str("a { b = x }, a { b = ${a.b}y }").unwrap().get_string("a.b"), Ok(String::from("xy")));
+    assert_eq!(from_str("foo { a = 1 }, foo = ${foo.a}").unwrap().get("foo"), Ok(Value::Int(1)));
+    assert_eq!(from_str("a = ${?a}x").unwrap().get_string("a"), Ok(String::from("x")));
+    assert_eq!(from_str("a = 1, b = ${a}, a = 2").unwrap().get("b"), Ok(Value::Int(2)));
+    // the same path looked up from inside and outside its own field
+    let conf = from_str("a = 1, b = ${a}, a = ${a}2, c = ${a}, d = [${a}, ${b}]").unwrap();
+    assert_eq!(conf.get_string("b"), Ok(String::from("12")));
+    assert_eq!(conf.get_string("c"), Ok(String::from("12")));
+    assert_eq!(conf.get_string_list("d"), Ok(vec![String::from("12"), String::from("12")]));
+    assert_eq!(from_str("a = ${a}"), Err(Error::UnresolvedSubstitution(String::from("a"))));
+}
+
+#[test] fn test_substitution_cycles() {
+    assert_eq!(
+        from_str("a = ${b}, b = ${a}"),
+        Err(Error::SubstitutionCycle(vec![String::from("b"), String::from("a"), String::from("b")]))
+    );
+    assert_eq!(
+        from_str("bar { foo = 42, baz = ${bar} }"),
+        Err(Error::SubstitutionCycle(vec![String::from("bar"), String::from("bar")]))
+    );
+    assert_eq!(
+        from_str("a = ${b}, b = ${a}").unwrap_err().to_string(),
+        "substitutions refer to each other in a cycle: b -> a -> b"
+    );
+}
+
+#[test] fn test_env_fallback() {
+    let options = ParseOptions::new().env({
+        let mut env = HashMap::new();
+        env.insert(String::from("HOME"), String::from("/home/user"));
+        env.insert(String::from("PATH"), String::from("/bin"));
+        env
+    });
+    let parse = |s| from_str_with_options(s, &options);
+
+    assert_eq!(parse("home = ${HOME}").unwrap().get("home"), Ok(Value::String(String::from("/home/user"))));
+    assert_eq!(parse("HOME = /root, home = ${HOME}").unwrap().get_string("home"), Ok(String::from("/root")));
+    assert_eq!(parse("PATH = ${PATH}\":/opt/bin\"").unwrap().get_string("PATH"), Ok(String::from("/bin:/opt/bin")));
+    assert_eq!(parse("a = ${?USER}").unwrap().get("a"), Err(Error::NotFound(String::from("a"))));
+    assert_eq!(parse("a = ${USER}"), Err(Error::UnresolvedSubstitution(String::from("USER"))));
+    assert_eq!(
+        from_str_with_options("home = ${HOME}", &options.clone().env_fallback(false)),
+        Err(Error::UnresolvedSubstitution(String::from("HOME")))
+    );
+}
+
+fn write_files(dir: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = env::temp_dir().join(format!("hocon-tests-{}-{}", dir, process::id()));
+    for &(name, contents) in files {
+        let path = root.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+    root
+}
+
+#[test] fn test_includes() {
+    let root = write_files("includes", &[
+        ("app.conf", r#"
+a = 1
+include "common.conf"
+b = 2
+nested { include file("conf/nested.conf") }
+include "missing.conf"
+"#),
+        ("common.conf", "a = 10, b = 20, c = 30"),
+        ("conf/nested.conf", "x = 1, y = ${x}, include \"sibling.conf\""),
+        ("conf/sibling.conf", "z = 3"),
+        ("required.conf", "include required(\"missing.conf\")"),
+        ("cycle.conf", "include \"cycle.conf\"")
+    ]);
+
+    let conf = from_file(root.join("app.conf")).unwrap();
+    assert_eq!(conf.get("a"), Ok(Value::Int(10)));
+    assert_eq!(conf.get("b"), Ok(Value::Int(2)));
+    assert_eq!(conf.get("c"), Ok(Value::Int(30)));
+    assert_eq!(conf.get("nested.y"), Ok(Value::Int(1)));
+    assert_eq!(conf.get("nested.z"), Ok(Value::Int(3)));
+
+    assert_eq!(
+        from_file(root.join("required.conf")),
+        Err(Error::IncludeNotFound(root.join("missing.conf").display().to_string()))
+    );
+    assert_eq!(
+        from_file(root.join("cycle.conf")),
+        Err(Error::IncludeCycle(root.join("cycle.conf").display().to_string()))
+    );
+    assert_eq!(
+        from_file(root.join("required.conf")).unwrap_err().to_string(),
+        format!("required include {} was not found", root.join("missing.conf").display())
+    );
+    assert_eq!(
+        from_file(root.join("cycle.conf")).unwrap_err().to_string(),
+        format!("{} includes itself", root.join("cycle.conf").display())
+    );
+    assert_eq!(from_str("include = 1").unwrap().get("include"), Ok(Value::Int(1)));
+}
+
+#[test] fn test_includers() {
+    let files = {
+        let mut m = HashMap::new();
+        m.insert(String::from("common.conf"), String::from("a = ${b}, include \"other.conf\""));
+        m.insert(String::from("other.conf"), String::from("b = 1"));
+        m
+    };
+    let options = ParseOptions::new().includer(files);
+    let conf = from_str_with_options("include \"common.conf\", c { include classpath(\"other.conf\") }", &options).unwrap();
+    assert_eq!(conf.get("a"), Ok(Value::Int(1)));
+    assert_eq!(conf.get("c.b"), Ok(Value::Int(1)));
+    assert_eq!(
+        from_str_with_options("include required(\"nope.conf\")", &options),
+        Err(Error::IncludeNotFound(String::from("nope.conf")))
+    );
+
+    struct Fixed;
+    impl Includer for Fixed {
+        fn load(&self, kind: IncludeKind, name: &str) -> Result<Option<Included>, Error> {
+            Ok(Some(Included::Value(hocon!({ kind: (format!("{:?}", kind)), name: name }))))
+        }
+    }
+    let conf = from_str_with_options("a { include url(\"http://x/y\") }", &ParseOptions::new().includer(Fixed)).unwrap();
+    assert_eq!(conf.get_string("a.kind"), Ok(String::from("Url")));
+    assert_eq!(conf.get_string("a.name"), Ok(String::from("http://x/y")));
+
+    struct Scalar;
+    impl Includer for Scalar {
+        fn load(&self, _: IncludeKind, _: &str) -> Result<Option<Included>, Error> {
+            Ok(Some(Included::Value(Value::Int(1))))
+        }
+    }
+    let options = ParseOptions::new().includer(Scalar);
+    assert_eq!(from_str_with_options("include \"a.conf\"", &options), Err(Error::IncompatibleType));
+    assert_eq!(from_str_with_options("include required(\"a.conf\")", &options), Err(Error::IncompatibleType));
+
+    // options can be built in one thread and used in another
+    fn send_sync<T: Send + Sync>(_: &T) {}
+    send_sync(&ParseOptions::new().includer(Fixed));
+    send_sync(&ConfigLoader::new().options(ParseOptions::new().includer(Fixed)));
+
+    let root = write_files("classpath", &[("lib/ref.conf", "x = 1")]);
+    let options = ParseOptions::new().includer(FileIncluder::new().classpath_root(root.join("lib")));
+    assert_eq!(from_str_with_options("include classpath(\"ref.conf\")", &options).unwrap().get("x"), Ok(Value::Int(1)));
+}
+
+#[test] fn test_append() {
+    let ints = |v: Vec<i64>| Ok(Value::Array(v.into_iter().map(Value::Int).collect()));
+    assert_eq!(from_str("a += 1").unwrap().get("a"), ints(vec![1]));
+    assert_eq!(from_str("a = [1], a += 2, a += 3").unwrap().get("a"), ints(vec![1, 2, 3]));
+    assert_eq!(from_str("x { a = [1] }, x { a += 2 }").unwrap().get("x.a"), ints(vec![1, 2]));
+    assert_eq!(from_str("a = [1], b = ${a}, a += 2").unwrap().get("b"), ints(vec![1, 2]));
+    assert_eq!(from_str("a = 1, a += 2").unwrap_err().to_string(), "a: IncompatibleType (set at <string>:1)");
+    assert_eq!(
+        without_origin(from_str("x { a = 1 }\nx.a += 2")),
+        Err(Error::AtPath(String::from("x.a"), Box::new(Error::IncompatibleType)))
+    );
+}
+
+#[test] fn test_concatenation() {
+    let string = |s: &str| Ok(Value::String(String::from(s)));
+    assert_eq!(from_str("a = hello world").unwrap().get("a"), string("hello world"));
+    assert_eq!(from_str("a = foo  bar\tbaz  // comment").unwrap().get("a"), string("foo  bar\tbaz"));
+    assert_eq!(from_str("a = 10 seconds").unwrap().get("a"), string("10 seconds"));
+    assert_eq!(from_str("a = 1.50 true null").unwrap().get("a"), string("1.50 true null"));
+    assert_eq!(from_str("x = world, a = hello ${x}\"!\"").unwrap().get("a"), string("hello world!"));
+    assert_eq!(from_str("a = foo ${?x} bar").unwrap().get("a"), string("foo  bar"));
+    assert_eq!(from_str("a = [hello world]").unwrap().get("a"), Ok(Value::Array(vec![Value::String(String::from("hello world"))])));
+
+    let ints = |v: Vec<i64>| Ok(Value::Array(v.into_iter().map(Value::Int).collect()));
+    assert_eq!(from_str("a = [1] [2, 3]").unwrap().get("a"), ints(vec![1, 2, 3]));
+    assert_eq!(from_str("a = [1], a = ${a} [2]").unwrap().get("a"), ints(vec![1, 2]));
+    assert_eq!(from_str("a = [1] ${?b} [2]").unwrap().get("a"), ints(vec![1, 2]));
+
+    let conf = from_str("a = {x: 1, z: {p: 1}} {y: 2, z: {q: 2}}").unwrap();
+    assert_eq!(conf.get("a.x"), Ok(Value::Int(1)));
+    assert_eq!(conf.get("a.y"), Ok(Value::Int(2)));
+    assert_eq!(conf.get("a.z.p"), Ok(Value::Int(1)));
+    assert_eq!(conf.get("a.z.q"), Ok(Value::Int(2)));
+    assert_eq!(from_str("b { x = 1 }, a = ${b} { y = 2 }").unwrap().get("a.x"), Ok(Value::Int(1)));
+
+    assert_eq!(from_str("a = [1] foo"), Err(Error::ConcatenationMismatch("array", "string")));
+    assert_eq!(from_str("a = {x: 1} [1]"), Err(Error::ConcatenationMismatch("object", "array")));
+    assert_eq!(from_str("b = [1], a = x ${b}"), Err(Error::ConcatenationMismatch("string", "array")));
+    assert_eq!(from_str("a = [1] foo").unwrap_err().to_string(), "cannot concatenate array with string");
+}
+
+#[cfg(feature = "serde")]
+#[test] fn test_deserialize() {
+    use ::from_str_as;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Listener { host: String, port: u16, tls: Option<bool> }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Mode { Fast, Careful { retries: u8 }, Named(String) }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Server { name: String, listeners: Vec<Listener>, modes: Vec<Mode>, limits: HashMap<String, f64> }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Settings { server: Server, debug: Option<String> }
+
+    let settings: Settings = from_str_as(r#"
+        server {
+            name = main
+            listeners = [
+                { host = localhost, port = 8080 }
+                { host = "0.0.0.0", port = "8443", tls = yes }
+            ]
+            modes = [fast, { careful { retries = 3 } }, { named = x }]
+            limits { cpu = 1.5, memory = 512 }
+        }
+    "#).unwrap();
+    assert_eq!(settings, Settings {
+        server: Server {
+            name: "main".to_string(),
+            listeners: vec![
+                Listener { host: "localhost".to_string(), port: 8080, tls: None },
+                Listener { host: "0.0.0.0".to_string(), port: 8443, tls: Some(true) }
+            ],
+            modes: vec![Mode::Fast, Mode::Careful { retries: 3 }, Mode::Named("x".to_string())],
+            limits: vec![("cpu".to_string(), 1.5), ("memory".to_string(), 512.0)].into_iter().collect()
+        },
+        debug: None
+    });
+
+    let err = from_str_as::<Settings>(r#"
+        server {
+            name = main
+            listeners = [{ host = a, port = 1 }, { host = b, port = 2 }, { host = c, port = http }]
+            modes = []
+            limits {}
+        }
+    "#).unwrap_err();
+    assert_eq!(err.to_string(), "server.listeners[2].port: expected integer");
+
+    let err = from_str_as::<Listener>("host = a, port = 70000").unwrap_err();
+    assert_eq!(err.to_string(), "port: integer 70000 is out of range for u16");
+    let err = from_str_as::<Server>("name = x, listeners = [{ host = a }]").unwrap_err();
+    assert_eq!(err.to_string(), "listeners[0]: missing field `port`");
+    let err = from_str_as::<HashMap<String, Mode>>("\"a.b\" = slow").unwrap_err();
+    assert_eq!(err, Error::Deserialize("\"a.b\"".to_string(),
+        "unknown variant `slow`, expected one of `fast`, `careful`, `named`".to_string()));
+}
+
+#[cfg(feature = "serde")]
+#[test] fn test_serialize() {
+    use ::to_value;
+
+    #[derive(Serialize)]
+    enum Mode { Fast, Careful { retries: u8 }, Named(String), Pair(i32, i32) }
+
+    #[derive(Serialize)]
+    struct Defaults { name: &'static str, port: u16, ratio: f32, tags: Vec<&'static str>, debug: Option<bool>, modes: Vec<Mode> }
+
+    let value = to_value(&Defaults {
+        name: "main",
+        port: 8080,
+        ratio: 0.5,
+        tags: vec!["a", "b"],
+        debug: None,
+        modes: vec![Mode::Fast, Mode::Careful { retries: 3 }, Mode::Named("x".to_string()), Mode::Pair(1, 2)]
+    }).unwrap();
+    assert_eq!(value, from_str(r#"
+        name = main, port = 8080, ratio = 0.5, tags = [a, b], debug = null
+        modes = [Fast, { Careful { retries = 3 } }, { Named = x }, { Pair = [1, 2] }]
+    "#).unwrap());
+
+    let mut limits = HashMap::new();
+    limits.insert(1, "one");
+    assert_eq!(to_value(&limits).unwrap(), from_str("\"1\" = one").unwrap());
+    assert_eq!(to_value(&u64::MAX), Err(Error::Serialize(format!("integer {} is out of range", u64::MAX))));
+
+    let original = from_str("a { b = [1, 2.5, true, null, x] }").unwrap();
+    assert_eq!(to_value(&original), Ok(original));
+}
+
+#[test] fn test_render() {
+    let mut value = from_str(r#"
+        server { name = main, port = 8080, ratio = 0.5, small = -2.5e-7, whole = 3.0 }
+        "a.b" = 1, "" = 2, "with space" = 3, "x//y" = 4, "q\"uote" = 5, "caf\u00e9" = 6
+        strings = ["true", "42", null, "tab\tand\u0001", "multi\nline", "ends\nwith\"", "has\n\"\"\"quotes"]
+        empty { obj {}, arr = [] }
+        nested = [[1, 2], [{ a = 1 }]]
+    "#).unwrap();
+    // whole floats too big or small for plain digits, which debug formatting writes as `1e16`
+    if let Value::Object(ref mut root) = value {
+        root.insert(String::from("big"), Value::Float(1e100));
+        root.insert(String::from("floats"), Value::Array(vec![Value::Float(1e16), Value::Float(1e21), Value::Float(-1e-7)]));
+    }
+
+    for &json in &[false, true] {
+        for &compact in &[false, true] {
+            let options = RenderOptions::new().json(json).compact(compact).indent(4);
+            let rendered = value.render(&options);
+            assert_eq!(from_str(&rendered), Ok(value.clone()), "rendered as:\n{}", rendered);
+            let empty = Value::Object(Map::new());
+            assert_eq!(empty.render(&options), "{}");
+            assert_eq!(from_str(&empty.render(&options)), Ok(empty));
+        }
+    }
+
+    let value = from_str("b { c = [1, 2], d = \"x y\" }, a = \"two\\nlines\", \"e.f\" = {}").unwrap();
+    assert_eq!(value.render(&RenderOptions::new()), r#"b {
+  c = [
+    1
+    2
+  ]
+  d = "x y"
+}
+a = """two
+lines"""
+"e.f" {}"#);
+    assert_eq!(value.render(&RenderOptions::new().compact(true)), r#"b{c=[1,2],d="x y"},a="two\nlines","e.f"{}"#);
+    assert_eq!(value.render(&RenderOptions::new().json(true).compact(true)),
+        r#"{"b":{"c":[1,2],"d":"x y"},"a":"two\nlines","e.f":{}}"#);
+    assert_eq!(value.render(&RenderOptions::new().json(true).indent(1)), r#"{
+ "b": {
+  "c": [
+   1,
+   2
+  ],
+  "d": "x y"
+ },
+ "a": "two\nlines",
+ "e.f": {}
+}"#);
+}
+
+#[test] fn test_key_order() {
+    let keys = |value: &Value| match *value {
+        Value::Object(ref obj) => obj.keys().cloned().collect::<Vec<_>>(),
+        _ => Vec::new()
+    };
+    let conf = from_str(r#"
+        z = 1, m = 2, a = 3
+        m = 4
+        x { c = 1, b = 2 }
+        x { a = 3, c = 4 }
+        y = ${x} { d = 5, b = 6 }
+        z { q = 1 }
+    "#).unwrap();
+    assert_eq!(keys(&conf), vec!["z", "m", "a", "x", "y"]);
+    assert_eq!(keys(&conf.get("x").unwrap()), vec!["c", "b", "a"]);
+    assert_eq!(keys(&conf.get("y").unwrap()), vec!["c", "b", "a", "d"]);
+    assert_eq!(conf.get_int("m"), Ok(4));
+    assert_eq!(
+        conf.render(&RenderOptions::new().compact(true)),
+        "z{q=1},m=4,a=3,x{c=4,b=2,a=3},y{c=4,b=6,a=3,d=5}"
+    );
+}
+
+#[test] fn test_with_fallback() {
+    let reference = from_str("a { b = 1, c = 2 }, d = [1], e = { f = 1 }, g = 1").unwrap();
+    let app = from_str("a { c = 3, h = 4 }, d = [2], e = 5").unwrap();
+    let merged = app.with_fallback(&reference);
+    assert_eq!(merged, from_str("a { b = 1, c = 3, h = 4 }, d = [2], e = 5, g = 1").unwrap());
+    assert_eq!(merged.render(&RenderOptions::new().compact(true)), "a{b=1,c=3,h=4},d=[2],e=5,g=1");
+    assert_eq!(Value::Int(1).with_fallback(&reference), Value::Int(1));
+    assert_eq!(reference.with_fallback(&Value::Null), reference);
+}
+
+#[test] fn test_config_loader() {
+    let root = write_files("loader", &[
+        ("reference.conf", "server { host = localhost, port = 80 }, url = \"http://\"${server.host}\":\"${server.port}"),
+        ("application.conf", "server.port = 8080, name = ${?APP_NAME}"),
+        ("local.conf", "# server.port = 9090\n")
+    ]);
+    let mut env = HashMap::new();
+    env.insert(String::from("APP_NAME"), String::from("app"));
+    let conf = ConfigLoader::new()
+        .value(from_str("server.host = default, timeout = 5").unwrap())
+        .file(root.join("reference.conf"))
+        .file(root.join("application.conf"))
+        .file(root.join("local.conf"))
+        .optional_file(root.join("overrides.conf"))
+        .str("server.host = example.com")
+        .options(ParseOptions::new().env(env))
+        .load()
+        .unwrap();
+    assert_eq!(conf.get_string("url"), Ok(String::from("http://example.com:8080")));
+    assert_eq!(conf.get_int("timeout"), Ok(5));
+    assert_eq!(conf.get_string("name"), Ok(String::from("app")));
+
+    match ConfigLoader::new().file(root.join("overrides.conf")).load() {
+        Err(err @ Error::Io(..)) => {
+            assert!(err.to_string().starts_with(&format!("could not read {}: ", root.join("overrides.conf").display())));
+        },
+        other => panic!("unexpected result {:?}", other)
+    }
+    fs::remove_dir_all(&root).unwrap();
+}
+
+/// Drops the origin that typed getters add to errors about values read from a document.
+fn without_origin<T>(result: Result<T, Error>) -> Result<T, Error> {
+    result.map_err(|err| {
+        match err {
+            Error::AtOrigin(err, _) => *err,
+            err => err
+        }
+    })
+}
+
+#[test] fn test_durations() {
+    use std::time::Duration;
+
+    let conf = from_str(r#"
+        timeout = 30s, ttl = 5 minutes, poll = 250ms, bare = 1500, quoted = "2 h"
+        tiny = 10ns, micro = 3 microseconds, fraction = 1.5 d, float = 2.5, day = 1day
+        unknown = 3 fortnights, huge = 999999999999999999 days, negative = -1s, text = soon
+    "#).unwrap();
+    assert_eq!(conf.get_duration("timeout"), Ok(Duration::from_secs(30)));
+    assert_eq!(conf.get_duration("ttl"), Ok(Duration::from_secs(300)));
+    assert_eq!(conf.get_duration("poll"), Ok(Duration::from_millis(250)));
+    assert_eq!(conf.get_duration("bare"), Ok(Duration::from_millis(1500)));
+    assert_eq!(conf.get_duration("quoted"), Ok(Duration::from_secs(7200)));
+    assert_eq!(conf.get_duration("tiny"), Ok(Duration::from_nanos(10)));
+    assert_eq!(conf.get_duration("micro"), Ok(Duration::from_micros(3)));
+    assert_eq!(conf.get_duration("fraction"), Ok(Duration::from_secs(36 * 3600)));
+    assert_eq!(conf.get_duration("float"), Ok(Duration::from_micros(2500)));
+    assert_eq!(conf.get_duration("day"), Ok(Duration::from_secs(86400)));
+    assert_eq!(
+        without_origin(conf.get_duration("unknown")),
+        Err(Error::UnknownUnit(String::from("unknown"), String::from("fortnights")))
+    );
+    assert_eq!(without_origin(conf.get_duration("huge")), Err(Error::OutOfRange(String::from("huge"))));
+    assert_eq!(without_origin(conf.get_duration("negative")), Err(Error::OutOfRange(String::from("negative"))));
+    assert_eq!(without_origin(conf.get_duration("text")), Err(Error::IncompatibleType));
+    assert_eq!(conf.get_duration_or("missing", Duration::from_secs(1)), Duration::from_secs(1));
+}
+
+#[test] fn test_bytes() {
+    let conf = from_str(r#"
+        cache = 512k, buffer = 64 MiB, disk = 2GB, bare = 100, fraction = 1.5G, word = 3 kibibytes
+        small = 10 kB, bytes = 7 bytes, max = 15EiB, huge = 16EiB, zetta = 1 ZB, negative = -1k
+        unknown = 5 parsecs, overflow = 18446744073709551615Y
+    "#).unwrap();
+    assert_eq!(conf.get_bytes("cache"), Ok(512 * 1024));
+    assert_eq!(conf.get_bytes("buffer"), Ok(64 * 1024 * 1024));
+    assert_eq!(conf.get_bytes("disk"), Ok(2_000_000_000));
+    assert_eq!(conf.get_bytes("bare"), Ok(100));
+    assert_eq!(conf.get_bytes("fraction"), Ok(3 << 29));
+    assert_eq!(conf.get_bytes("word"), Ok(3072));
+    assert_eq!(conf.get_bytes("small"), Ok(10_000));
+    assert_eq!(conf.get_bytes("bytes"), Ok(7));
+    assert_eq!(conf.get_bytes("max"), Ok(15 << 60));
+    assert_eq!(without_origin(conf.get_bytes("huge")), Err(Error::OutOfRange(String::from("huge"))));
+    assert_eq!(without_origin(conf.get_bytes("zetta")), Err(Error::OutOfRange(String::from("zetta"))));
+    assert_eq!(without_origin(conf.get_bytes("negative")), Err(Error::OutOfRange(String::from("negative"))));
+    assert_eq!(without_origin(conf.get_bytes("overflow")), Err(Error::OutOfRange(String::from("overflow"))));
+    assert_eq!(
+        without_origin(conf.get_bytes("unknown")),
+        Err(Error::UnknownUnit(String::from("unknown"), String::from("parsecs")))
+    );
+    assert_eq!(conf.get_bytes_or("missing", 42), 42);
+}
+
+#[test] fn test_lists() {
+    use std::time::Duration;
+
+    let conf = from_str(r#"
+        flags = [true, "yes", off], ints = [1, 2, 3], floats = [1, 2.5], strings = [a, "b c"]
+        timeouts = [10s, 5 minutes, 250], sizes = [1k, 2MB], servers = [{ host = a }, { host = b }]
+        indexed { "2" = c, "0" = a, "10" = d, "1" = b, other = x, "01" = y, "+1" = z }
+        mixed = [1, two, 3], units = [1s, 3 fortnights], scalar = 1, named { a = 1 }
+    "#).unwrap();
+    assert_eq!(conf.get_bool_list("flags"), Ok(vec![true, true, false]));
+    assert_eq!(conf.get_int_list("ints"), Ok(vec![1, 2, 3]));
+    assert_eq!(conf.get_float_list("floats"), Ok(vec![1.0, 2.5]));
+    assert_eq!(conf.get_string_list("strings"), Ok(vec![String::from("a"), String::from("b c")]));
+    assert_eq!(
+        conf.get_duration_list("timeouts"),
+        Ok(vec![Duration::from_secs(10), Duration::from_secs(300), Duration::from_millis(250)])
+    );
+    assert_eq!(conf.get_bytes_list("sizes"), Ok(vec![1024, 2_000_000]));
+    let servers = conf.get_object_list("servers").unwrap();
+    assert_eq!(servers[1].get_string("host"), Ok(String::from("b")));
+    assert_eq!(
+        conf.get_string_list("indexed"),
+        Ok(vec![String::from("a"), String::from("b"), String::from("c"), String::from("d")])
+    );
+
+    assert_eq!(
+        without_origin(conf.get_int_list("mixed")),
+        Err(Error::ListElement(String::from("mixed"), 1, Box::new(Error::IncompatibleType)))
+    );
+    assert_eq!(
+        conf.get_duration_list("units").unwrap_err().to_string(),
+        "element 1 of units: unknown unit 'fortnights' at units[1] (set at <string>:5)"
+    );
+    assert_eq!(without_origin(conf.get_object_list("ints")), Err(Error::ListElement(String::from("ints"), 0, Box::new(Error::IncompatibleType))));
+    assert_eq!(without_origin(conf.get_list("scalar")), Err(Error::IncompatibleType));
+    assert_eq!(without_origin(conf.get_list("named")), Err(Error::IncompatibleType));
+}
+
+#[test] fn test_array_paths() {
+    let conf = from_str(r#"
+        servers = [{ host = a, ports = [80, 443] }, { host = b }, { host = c }]
+        indexed { "0" = x }
+        "dotted.key" = [1]
+    "#).unwrap();
+    assert_eq!(conf.get_string("servers[0].host"), Ok(String::from("a")));
+    assert_eq!(conf.get_string("servers.1.host"), Ok(String::from("b")));
+    assert_eq!(conf.get_string("servers[-1].host"), Ok(String::from("c")));
+    assert_eq!(conf.get_string("servers.-3.host"), Ok(String::from("a")));
+    assert_eq!(conf.get_int("servers[0].ports[1]"), Ok(443));
+    assert_eq!(conf.get_int("servers[0].ports[-2]"), Ok(80));
+    assert_eq!(conf.get_string("indexed[0]"), Ok(String::from("x")));
+    assert_eq!(conf.get_int("\"dotted.key\"[0]"), Ok(1));
+
+    assert_eq!(conf.get("servers[3]"), Err(Error::IndexOutOfBounds(String::from("servers[3]"), 3)));
+    assert_eq!(conf.get("servers[-4].host"), Err(Error::IndexOutOfBounds(String::from("servers[-4].host"), -4)));
+    assert_eq!(conf.get("servers.5"), Err(Error::IndexOutOfBounds(String::from("servers.5"), 5)));
+    assert_eq!(conf.get("servers[0].name"), Err(Error::NotFound(String::from("servers[0].name"))));
+    assert_eq!(conf.get("servers.first"), Err(Error::NotFound(String::from("servers.first"))));
+    assert_eq!(conf.get("servers[x]"), Err(Error::PathError(String::from("servers[x]"))));
+    assert_eq!(conf.get("servers[0"), Err(Error::PathError(String::from("servers[0"))));
+    assert_eq!(conf.get("servers[0]host"), Err(Error::PathError(String::from("servers[0]host"))));
+}
+
+#[test] fn test_borrowing_accessors() {
+    let mut conf = from_str("a { b = [1, { c = text }] }, n = 1").unwrap();
+    assert_eq!(conf.get_ref("a.b[1].c"), Ok(&Value::String(String::from("text"))));
+    assert_eq!(conf.get_str("a.b[-1].c"), Ok("text"));
+    assert_eq!(without_origin(conf.get_str("n")), Err(Error::IncompatibleType));
+    assert_eq!(conf.get_ref("a.x"), Err(Error::NotFound(String::from("a.x"))));
+    assert!(::std::ptr::eq(conf.get_ref("a").unwrap(), conf.get_ref("a").unwrap()));
+
+    *conf.get_mut("a.b[0]").unwrap() = Value::Int(5);
+    if let Value::Object(ref mut obj) = *conf.get_mut("a.b.1").unwrap() {
+        obj.insert(String::from("d"), Value::Boolean(true));
+    }
+    assert_eq!(conf, from_str("a { b = [5, { c = text, d = true }] }, n = 1").unwrap());
+    assert_eq!(conf.get_mut("a.b[2]"), Err(Error::IndexOutOfBounds(String::from("a.b[2]"), 2)));
+    assert_eq!(conf.get_mut("n.x"), Err(Error::NotFound(String::from("n.x"))));
+    assert_eq!(conf.get_mut("a..b"), Err(Error::PathError(String::from("a..b"))));
+}
+
+#[test] fn test_origins() {
+    let files = {
+        let mut m = HashMap::new();
+        m.insert(String::from("ports.conf"), String::from("# overridden below\nhttp = 80\nhttps = 443"));
+        m
+    };
+    let options = ParseOptions::new().includer(files);
+    let conf = from_str_with_options(r#"
+        # Where the server listens.
+        // Must be reachable from the load balancer.
+        host = localhost
+
+        ports {
+            include "ports.conf"
+            http = 8080
+        }
+        servers = [
+            { name = a }
+        ]
+        host = ${?HOCON_TEST_NO_SUCH_VAR}
+        timeout = soon
+    "#, &options).unwrap();
+
+    let host = conf.origin("host").unwrap();
+    assert_eq!((&*host.source, host.start_line, host.end_line), ("<string>", 4, 4));
+    assert_eq!(host.comments, vec!["Where the server listens.", "Must be reachable from the load balancer."]);
+    assert_eq!(host.to_string(), "<string>:4");
+    assert_eq!(conf.origin("ports").unwrap().to_string(), "<string>:6-9");
+    assert_eq!(conf.origin("ports.http").unwrap().to_string(), "<string>:8");
+    assert_eq!(conf.origin("ports.https").unwrap().to_string(), "ports.conf:3");
+    assert!(conf.origin("ports.https").unwrap().comments.is_empty());
+    assert_eq!(conf.origin("servers[0].name").unwrap().to_string(), "<string>:11");
+    assert_eq!(conf.origin("servers[0]").unwrap().to_string(), "<string>:10-12");
+    assert_eq!(conf.origin("missing"), None);
+    // origins stay out of the debug output
+    assert_eq!(format!("{:?}", from_str("a { b = 1, c = 2 }").unwrap()), r#"Object({"a": Object({"b": Int(1), "c": Int(2)})})"#);
+
+    assert_eq!(
+        conf.get_int("timeout"),
+        Err(Error::AtOrigin(Box::new(Error::IncompatibleType), conf.origin("timeout").unwrap().clone()))
+    );
+    assert_eq!(conf.get_bytes("timeout").unwrap_err().to_string(), "IncompatibleType (set at <string>:14)");
+
+    // merging keeps the origin of the value that won
+    let overrides = from_str("ports.http = 9090").unwrap();
+    let merged = overrides.with_fallback(&conf);
+    assert_eq!(merged.origin("ports.http").unwrap().to_string(), "<string>:1");
+    assert_eq!(merged.origin("ports.https").unwrap().to_string(), "ports.conf:3");
+    assert_eq!(merged.origin("host").unwrap().start_line, 4);
+    let merged = ConfigLoader::new().value(conf.clone()).str("a = 1").load().unwrap();
+    assert_eq!(merged.origin("ports.https").unwrap().to_string(), "ports.conf:3");
+
+    let mut built = Map::new();
+    built.insert(String::from("a"), Value::Int(1));
+    assert_eq!(Value::Object(built).origin("a"), None);
+
+    // changing a value in place keeps its origin, replacing it forgets it
+    let mut map = match from_str("a { b = 1 }\nc = 2\nd = 3").unwrap() {
+        Value::Object(map) => map,
+        _ => unreachable!()
+    };
+    *map.get_mut("a").unwrap().get_mut("b").unwrap() = Value::Int(5);
+    assert_eq!(map.origin("a").unwrap().start_line, 1);
+    map.insert(String::from("c"), Value::Int(4));
+    assert_eq!(map.origin("c"), None);
+    map.shift_remove("d");
+    map.get_or_insert_with(String::from("d"), || Value::Int(6));
+    assert_eq!(map.origin("d"), None);
+    assert_eq!(Value::Object(map), hocon!({ a: { b: 5 }, c: 4, d: 6 }));
+}
+
+#[test] fn test_document() {
+    let text = r#"# Server settings
+server {
+    host: "localhost"   // for now
+    port = 80
+
+    # how long to wait
+    timeout = 30s
+}
+paths += "/tmp", "quoted.key" = x
+"#;
+    let mut doc: Document = text.parse().unwrap();
+    assert_eq!(doc.to_string(), text);
+
+    doc.set("server.port", &Value::Int(8080)).unwrap();
+    doc.set("server.host", &Value::String(String::from("example.com"))).unwrap();
+    doc.set("paths", &Value::Array(vec![Value::String(String::from("/var"))])).unwrap();
+    assert_eq!(doc.to_string(), r#"# Server settings
+server {
+    host: "example.com"   // for now
+    port = 8080
+
+    # how long to wait
+    timeout = 30s
+}
+paths = ["/var"], "quoted.key" = x
+"#);
+
+    doc.insert("server.tls.enabled", &Value::Boolean(true)).unwrap();
+    doc.insert("client", &hocon!({ retries: 3 })).unwrap();
+    doc.set("quoted.key", &Value::Null).unwrap();
+    assert_eq!(doc.to_string(), r#"# Server settings
+server {
+    host: "example.com"   // for now
+    port = 8080
+
+    # how long to wait
+    timeout = 30s
+    tls.enabled = true
+}
+paths = ["/var"], "quoted.key" = x, client = {retries=3}, quoted.key = null
+"#);
+
+    doc.remove("server.timeout").unwrap();
+    doc.remove("\"quoted.key\"").unwrap();
+    doc.remove("quoted").unwrap();
+    assert_eq!(doc.to_string(), r#"# Server settings
+server {
+    host: "example.com"   // for now
+    port = 8080
+
+    # how long to wait
+    tls.enabled = true
+}
+paths = ["/var"], client = {retries=3}
+"#);
+    assert_eq!(doc.remove("server.timeout"), Err(Error::NotFound(String::from("server.timeout"))));
+    assert_eq!(doc.set("server..port", &Value::Null), Err(Error::PathError(String::from("server..port"))));
+
+    let value = doc.to_value().unwrap();
+    assert_eq!(value.get_int("server.port"), Ok(8080));
+    assert_eq!(value.get_bool("server.tls.enabled"), Ok(true));
+
+    let mut doc: Document = "a { b = 1 }\n".parse().unwrap();
+    doc.set("a", &Value::Int(2)).unwrap();
+    doc.insert("c", &Value::Object(Map::new())).unwrap();
+    doc.insert("c.d", &Value::Int(3)).unwrap();
+    assert_eq!(doc.to_string(), "a = 2\nc = { d = 3 }\n");
+    assert!(Document::parse("a = ").is_err());
+
+    let mut doc: Document = "x { a = 1, b = 2 }, y = 3 # last".parse().unwrap();
+    doc.remove("x.a").unwrap();
+    doc.remove("y").unwrap();
+    assert_eq!(doc.to_string(), "x { b = 2 } # last");
+
+    let mut doc: Document = "a = 1\n".parse().unwrap();
+    doc.remove("a").unwrap();
+    assert_eq!(doc.to_string(), "");
+    assert_eq!(doc.to_value(), Ok(Value::Object(Map::new())));
+    doc.insert("a", &Value::Int(2)).unwrap();
+    assert_eq!(doc.to_string(), "a = 2\n");
+
+    let mut doc: Document = "# nothing yet\n".parse().unwrap();
+    assert_eq!(doc.to_value(), Ok(Value::Object(Map::new())));
+    doc.insert("a", &Value::Int(1)).unwrap();
+    assert_eq!(doc.to_string(), "# nothing yet\na = 1\n");
+
+    let mut doc: Document = "a { }".parse().unwrap();
+    doc.insert("a.b", &Value::Int(1)).unwrap();
+    assert_eq!(doc.to_string(), "a { b = 1 }");
+
+    let mut doc: Document = "{\n  include \"a.conf\"\n  a { b = \"}\" }\n} # }\n".parse().unwrap();
+    doc.insert("a.c", &Value::Int(1)).unwrap();
+    doc.insert("d", &Value::Int(2)).unwrap();
+    assert_eq!(doc.to_string(), "{\n  include \"a.conf\"\n  a { b = \"}\", c = 1 }\n  d = 2\n} # }\n");
+
+    // set replaces the value instead of merging it with objects set before
+    let mut doc: Document = "a { b = 1 }\n# last\na { c = 2 }\na.d += 3, e = 4\n".parse().unwrap();
+    doc.set("a", &hocon!({ z: 3 })).unwrap();
+    assert_eq!(doc.to_string(), "# last\na {\n  z = 3\n}\ne = 4\n");
+    assert_eq!(doc.to_value(), Ok(hocon!({ a: { z: 3 }, e: 4 })));
+}
+
+#[test] fn test_value_construction() {
+    let port = 8080;
+    let mut conf = hocon!({
+        server: { host: "localhost", port: port, "tls.enabled": false },
+        paths: ["/a", "/b",],
+        ratio: -0.5,
+        empty: {},
+        nothing: null,
+    });
+    assert_eq!(conf, from_str(r#"
+        server { host = localhost, port = 8080, "tls.enabled" = false }
+        paths = ["/a", "/b"], ratio = -0.5, empty {}, nothing = null
+    "#).unwrap());
+    assert_eq!(hocon!([1, [true, "x"], {}]), from_str("a = [1, [true, x], {}]").unwrap().get("a").unwrap());
+    assert_eq!(hocon!(port * 2), Value::Int(16160));
+
+    conf.set("server.port", Value::from(9090)).unwrap();
+    conf.set("client.retry.max", hocon!(3)).unwrap();
+    conf.set("ratio.min", Value::from(0.1)).unwrap();
+    conf.set("paths[-1]", Value::from("/c")).unwrap();
+    assert_eq!(conf.get_int("server.port"), Ok(9090));
+    assert_eq!(conf.get("client"), Ok(hocon!({ retry: { max: 3 } })));
+    assert_eq!(conf.get("ratio"), Ok(hocon!({ min: 0.1 })));
+    assert_eq!(conf.get("paths"), Ok(Value::from(vec!["/a", "/c"])));
+    assert_eq!(conf.set("paths[2]", Value::Null), Err(Error::IndexOutOfBounds(String::from("paths[2]"), 2)));
+
+    assert_eq!(conf.remove("server.host"), Ok(Value::from("localhost")));
+    assert_eq!(conf.remove("paths[0]"), Ok(Value::from("/a")));
+    assert_eq!(conf.get("paths"), Ok(hocon!(["/c"])));
+    assert_eq!(conf.remove("server.host"), Err(Error::NotFound(String::from("server.host"))));
+    assert_eq!(conf.remove("paths[1]"), Err(Error::IndexOutOfBounds(String::from("paths[1]"), 1)));
+    assert_eq!(conf.remove("nothing.x"), Err(Error::NotFound(String::from("nothing.x"))));
+
+    let mut parsed = from_str("# the port\nport = 80").unwrap();
+    parsed.set("port", Value::from(81)).unwrap();
+    assert_eq!(parsed.origin("port"), None);
+
+    let mut map = HashMap::new();
+    map.insert(String::from("b"), vec![1u8, 2]);
+    map.insert(String::from("a"), vec![]);
+    let value = Value::from(map);
+    assert_eq!(value, hocon!({ a: [], b: [1, 2] }));
+    assert_eq!(value.render(&RenderOptions::new().compact(true)), "a=[],b=[1,2]");
+    assert_eq!(Value::from('x'), Value::String(String::from("x")));
+    assert_eq!(Value::from(String::from("s")), Value::from("s"));
+    assert_eq!(Value::try_from(5u64), Ok(Value::Int(5)));
+    assert_eq!(Value::try_from(7usize), Ok(Value::Int(7)));
+    assert!(Value::try_from(u64::MAX).is_err());
+    assert!(Value::try_from(i128::MIN).is_err());
+
+    // literals longer than the macro recursion limit
+    let fields = hocon!({
+        f0: 0, f1: 1, f2: 2, f3: 3, f4: 4, f5: 5, f6: 6, f7: 7, f8: 8, f9: 9,
+        f10: 10, f11: 11, f12: 12, f13: 13, f14: 14, f15: 15, f16: 16, f17: 17, f18: 18, f19: 19,
+        f20: 20, f21: 21, f22: 22, f23: 23, f24: 24, f25: 25, f26: 26, f27: 27, f28: 28, f29: 29,
+        f30: 30, f31: 31, f32: 32, f33: 33, f34: 34, f35: 35, f36: 36, f37: 37, f38: 38, f39: 39,
+        f40: 40, f41: 41, f42: 42, f43: 43, f44: 44, f45: 45, f46: 46, f47: 47, f48: 48, f49: 49,
+        f50: 50, f51: 51, f52: 52, f53: 53, f54: 54, f55: 55, f56: 56, f57: 57, f58: 58, f59: 59,
+        f60: 60, f61: 61, f62: 62, f63: 63, f64: 64, f65: 65, f66: 66, f67: 67, f68: 68, f69: 69
+    });
+    assert_eq!(fields, Value::Object((0..70).map(|i| (format!("f{}", i), Value::Int(i))).collect()));
+    let elements = hocon!([
+        -1, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18,
+        19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38,
+        39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58,
+        59, 60, 61, 62, 63, 64, 65, 66, 67, 68, 69, 70, 71, 72, 73, 74, 75, 76, 77, 78,
+        79, 80, 81, 82, 83, 84, 85, 86, 87, 88, 89, 90, 91, 92, 93, 94, 95, 96, 97, 98
+    ]);
+    assert_eq!(elements, Value::Array((-1..99).map(Value::Int).collect()));
+}
+
+#[test] fn test_config() {
+    let conf = Config::new(from_str(r#"
+        db {
+            primary { host = db1, port = 5432, timeout = 5s, replicas = [a, b], "pool.size" = 10, comment = null }
+            empty {}
+        }
+        name = app
+    "#).unwrap()).unwrap();
+    let primary = conf.get_config("db.primary").unwrap();
+    assert_eq!(primary.prefix(), "db.primary");
+    assert_eq!(primary.get_string("host"), Ok(String::from("db1")));
+    assert_eq!(primary.get_int("\"pool.size\""), Ok(10));
+    assert_eq!(primary.get_duration("timeout"), Ok(::std::time::Duration::from_secs(5)));
+    assert_eq!(primary.get_int_or("missing", 3), 3);
+    assert_eq!(primary.get("replicas[1]"), Ok(Value::from("b")));
+    assert_eq!(primary.origin("port").unwrap().start_line, 3);
+
+    // errors name the path from the root
+    assert_eq!(primary.get("missing"), Err(Error::NotFound(String::from("db.primary.missing"))));
+    assert_eq!(
+        primary.get_int_list("replicas").unwrap_err().to_string(),
+        "element 0 of db.primary.replicas: IncompatibleType (set at <string>:3)"
+    );
+    assert_eq!(
+        conf.get_config("db").and_then(|db| db.get_config("primary.host")).unwrap_err().to_string(),
+        "db.primary.host: IncompatibleType (set at <string>:3)"
+    );
+    match primary.get_int("host") {
+        Err(Error::AtPath(path, err)) => {
+            assert_eq!(path, "db.primary.host");
+            assert_eq!(without_origin(Err::<(), _>(*err)), Err(Error::IncompatibleType));
+        },
+        other => panic!("unexpected result {:?}", other)
+    }
+    assert_eq!(primary.get_bytes("host").unwrap_err().to_string(), "db.primary.host: IncompatibleType (set at <string>:3)");
+    assert_eq!(conf.get_config("db.nope").unwrap_err(), Error::NotFound(String::from("db.nope")));
+
+    assert!(primary.has_path("host"));
+    assert!(!primary.has_path("comment"));
+    assert!(!primary.has_path("host.x"));
+    assert!(conf.has_path("db.primary"));
+    assert!(!primary.is_empty());
+    assert!(conf.get_config("db.empty").unwrap().is_empty());
+    assert_eq!(conf.keys().collect::<Vec<_>>(), vec!["db", "name"]);
+    assert_eq!(primary.keys().count(), 6);
+
+    let entries: Vec<(String, Value)> = conf.get_config("db").unwrap().entry_set().into_iter()
+        .map(|(path, value)| (path, value.clone()))
+        .collect();
+    assert_eq!(entries, vec![
+        (String::from("primary.host"), Value::from("db1")),
+        (String::from("primary.port"), Value::from(5432)),
+        (String::from("primary.timeout"), Value::from("5s")),
+        (String::from("primary.replicas"), hocon!(["a", "b"])),
+        (String::from("primary.\"pool.size\""), Value::from(10))
+    ]);
+    assert_eq!(primary.value(), &conf.get("db.primary").unwrap());
+    assert_eq!(Config::new(Value::Int(1)), Err(Error::IncompatibleType));
+}
+
+#[test] fn test_flatten() {
+    let conf = from_str(r#"
+        server { host = localhost, ports = [80, 443] }
+        "a.b" { "" = 1, "x y" = 2, "$weird" = 3 }
+        servers = [{ host = a }, { host = b }]
+        empty {}, none = null, no_list = []
+    "#).unwrap();
+    let flat = conf.flatten();
+    assert_eq!(flat, vec![
+        (String::from("server.host"), Value::from("localhost")),
+        (String::from("server.ports"), hocon!([80, 443])),
+        (String::from("\"a.b\".\"\""), Value::from(1)),
+        (String::from("\"a.b\".\"x y\""), Value::from(2)),
+        (String::from("\"a.b\".\"$weird\""), Value::from(3)),
+        (String::from("servers"), hocon!([{ host: "a" }, { host: "b" }])),
+        (String::from("empty"), hocon!({})),
+        (String::from("none"), Value::Null),
+        (String::from("no_list"), hocon!([]))
+    ]);
+    assert_eq!(Value::from_flat(flat), Ok(conf.clone()));
+
+    let options = FlattenOptions::new().indexed_arrays(true);
+    let flat = conf.flatten_with_options(&options);
+    let paths: Vec<&str> = flat.iter().map(|(path, _)| path.as_str()).collect();
+    assert_eq!(paths, vec![
+        "server.host", "server.ports.0", "server.ports.1", "\"a.b\".\"\"", "\"a.b\".\"x y\"",
+        "\"a.b\".\"$weird\"", "servers.0.host", "servers.1.host", "empty", "none", "no_list"
+    ]);
+    for (path, value) in &flat {
+        assert_eq!(conf.get(path).as_ref(), Ok(value));
+    }
+    assert_eq!(Value::from_flat_with_options(flat.clone(), &options), Ok(conf.clone()));
+    assert_eq!(Value::from_flat(flat).unwrap().get("server.ports"), Ok(hocon!({ "0": 80, "1": 443 })));
+
+    let rebuilt = Value::from_flat(vec![
+        (String::from("a.b"), Value::from(1)),
+        (String::from("a.b.c"), Value::from(2)),
+        (String::from("d"), Value::from(3))
+    ]);
+    assert_eq!(rebuilt, Ok(hocon!({ a: { b: { c: 2 } }, d: 3 })));
+    assert_eq!(
+        Value::from_flat(vec![(String::from("a..b"), Value::Null)]),
+        Err(Error::PathError(String::from("a..b")))
+    );
+    assert_eq!(Value::Int(1).flatten(), vec![(String::new(), Value::Int(1))]);
+    assert_eq!(Value::from_flat(Value::Int(1).flatten()), Ok(Value::Int(1)));
+}
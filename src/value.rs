@@ -1,6 +1,16 @@
+use indexmap::{map, IndexMap};
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::iter::FromIterator;
+use std::num::TryFromIntError;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
 use ::error::Error;
-use ::parser::json_object_path;
+use ::origin::Origin;
+use ::parser::{lookup_path, PathElement};
+use ::resolve::merge;
 use nom::IResult;
 
 #[derive(Debug, PartialEq, Clone)]
@@ -11,60 +21,296 @@ pub enum Value {
     Float(f64),
     String(String),
     Array(Vec<Value>),
-    Object(HashMap<String, Value>)
+    /// Fields in the order their keys first appear in the document. Keys set
+    /// again later, by a repeated field, a merge or an include, keep their position.
+    Object(Map)
+}
+
+/// Fields of an object, along with where each was set when the object comes
+/// from a document. It reads like the `IndexMap` it wraps, but is only changed
+/// through its own methods so that origins are forgotten when a key is set or
+/// removed. Origins are not compared.
+#[derive(Clone, Default)]
+pub struct Map {
+    entries: IndexMap<String, Value>,
+    origins: HashMap<String, Arc<Origin>>
+}
+
+impl Map {
+
+    pub fn new() -> Map {
+        Map::default()
+    }
+
+    /// Where the value of `key` was set, if it was read from a document.
+    pub fn origin(&self, key: &str) -> Option<&Origin> {
+        self.origins.get(key).map(|origin| &**origin)
+    }
+
+    /// Sets `key`, keeping its position if it is already there.
+    pub fn insert(&mut self, key: String, value: Value) -> Option<Value> {
+        self.origins.remove(&key);
+        self.entries.insert(key, value)
+    }
+
+    /// Removes `key`, shifting the keys after it to keep their order.
+    pub fn shift_remove(&mut self, key: &str) -> Option<Value> {
+        self.origins.remove(key);
+        self.entries.shift_remove(key)
+    }
+
+    /// Borrows the value of `key` to change it in place, keeping its origin.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        self.entries.get_mut(key)
+    }
+
+    /// Borrows the value of `key`, adding it at the end with `default` if it is missing.
+    pub fn get_or_insert_with<F: FnOnce() -> Value>(&mut self, key: String, default: F) -> &mut Value {
+        self.entries.entry(key).or_insert_with(default)
+    }
+
+    pub(crate) fn insert_with_origin(&mut self, key: String, value: Value, origin: Option<Arc<Origin>>) {
+        match origin {
+            Some(origin) => self.origins.insert(key.clone(), origin),
+            None => self.origins.remove(&key)
+        };
+        self.entries.insert(key, value);
+    }
+
+    pub(crate) fn shift_remove_with_origin(&mut self, key: &str) -> Option<(Value, Option<Arc<Origin>>)> {
+        let origin = self.origins.remove(key);
+        self.entries.shift_remove(key).map(|value| (value, origin))
+    }
+
+    pub(crate) fn origin_arc(&self, key: &str) -> Option<Arc<Origin>> {
+        self.origins.get(key).cloned()
+    }
+
+    /// Entries in order, each with its origin.
+    pub(crate) fn into_entries(self) -> Vec<(String, Value, Option<Arc<Origin>>)> {
+        let mut origins = self.origins;
+        self.entries.into_iter().map(|(key, value)| {
+            let origin = origins.remove(&key);
+            (key, value, origin)
+        }).collect()
+    }
+
+}
+
+impl fmt::Debug for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.entries.fmt(f)
+    }
+}
+
+impl PartialEq for Map {
+    fn eq(&self, other: &Map) -> bool {
+        self.entries == other.entries
+    }
+}
+
+impl Deref for Map {
+    type Target = IndexMap<String, Value>;
+
+    fn deref(&self) -> &IndexMap<String, Value> {
+        &self.entries
+    }
+}
+
+impl From<IndexMap<String, Value>> for Map {
+    fn from(entries: IndexMap<String, Value>) -> Map {
+        Map { entries, origins: HashMap::new() }
+    }
+}
+
+impl FromIterator<(String, Value)> for Map {
+    fn from_iter<I: IntoIterator<Item = (String, Value)>>(iter: I) -> Map {
+        Map::from(iter.into_iter().collect::<IndexMap<_, _>>())
+    }
+}
+
+impl Extend<(String, Value)> for Map {
+    fn extend<I: IntoIterator<Item = (String, Value)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl IntoIterator for Map {
+    type Item = (String, Value);
+    type IntoIter = map::IntoIter<String, Value>;
+
+    fn into_iter(self) -> map::IntoIter<String, Value> {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Map {
+    type Item = (&'a String, &'a Value);
+    type IntoIter = map::Iter<'a, String, Value>;
+
+    fn into_iter(self) -> map::Iter<'a, String, Value> {
+        self.entries.iter()
+    }
 }
 
 impl Value {
 
+    /// Looks up the value at `path`. Besides keys separated by dots, a path can
+    /// index into arrays with `servers[0]` or `servers.0`, and count from the
+    /// end of an array with negative indices like `servers[-1]`.
     pub fn get(&self, path: &str) -> Result<Value, Error> {
-        match json_object_path(path.as_bytes()) {
-            IResult::Done(ref rest, ref path_parts) if rest.len() == 0 => {
-                let v: Option<&Value> = path_parts.iter().fold(Some(self), |v, key| {
-                    match v {
-                        Some(&Value::Object(ref obj)) => {
-                            obj.get(key)
-                        },
-                        _ => None
-                    }
-                });
-                v.map(|vr| (*vr).clone()).ok_or(Error::NotFound(String::from(path)))
+        self.get_ref(path).cloned()
+    }
+
+    /// Like `get`, but borrows the value instead of cloning it.
+    pub fn get_ref(&self, path: &str) -> Result<&Value, Error> {
+        let path_parts = parse_lookup_path(path)?;
+        self.walk(path, &path_parts)
+    }
+
+    /// Like `get`, but borrows the value mutably so it can be changed in place.
+    pub fn get_mut(&mut self, path: &str) -> Result<&mut Value, Error> {
+        let path_parts = parse_lookup_path(path)?;
+        self.walk_mut(path, &path_parts)
+    }
+
+    /// Sets the value at `path`, adding the objects on the way that are
+    /// missing. Any other value on the way is replaced by an object, the way
+    /// a field setting a path inside it in a document would. Array elements
+    /// can be replaced by index, but not added.
+    pub fn set(&mut self, path: &str, value: Value) -> Result<(), Error> {
+        let path_parts = parse_lookup_path(path)?;
+        let (last, parents) = match path_parts.split_last() {
+            Some(split) => split,
+            None => {
+                *self = value;
+                return Ok(());
+            }
+        };
+        let parent = parents.iter().try_fold(self, |v, part| slot(v, part, path))?;
+        match (parent, array_index(last)) {
+            (&mut Value::Array(ref mut elems), Some(i)) => {
+                *element_mut(elems, i).ok_or_else(|| Error::IndexOutOfBounds(String::from(path), i))? = value;
             },
-            r => {
-                println!("{:?}", r);
-                Err(Error::PathError(String::from(path)))
+            (parent, _) => {
+                object_fields(parent).insert(object_key(last), value);
             }
         }
+        Ok(())
     }
 
-    pub fn get_bool(&self, path: &str) -> Result<bool, Error> {
-        self.get(path).and_then(|v| {
-            match v {
-                Value::Boolean(b) => Ok(b),
-                Value::String(s) => {
-                    if &s == "true" || &s == "yes" || &s == "on" {
-                        Ok(true)
-                    } else if &s == "false" || &s == "no" || &s == "off" {
-                        Ok(false)
-                    } else {
-                        Err(Error::IncompatibleType)
-                    }
+    /// Removes the value at `path` and returns it. Array elements after a
+    /// removed one move back by one.
+    pub fn remove(&mut self, path: &str) -> Result<Value, Error> {
+        let path_parts = parse_lookup_path(path)?;
+        let not_found = || Error::NotFound(String::from(path));
+        let (last, parents) = path_parts.split_last().ok_or_else(not_found)?;
+        match (self.walk_mut(path, parents)?, array_index(last)) {
+            (&mut Value::Object(ref mut obj), _) => obj.shift_remove(&object_key(last)).ok_or_else(not_found),
+            (&mut Value::Array(ref mut elems), Some(i)) => {
+                match position(elems.len(), i) {
+                    Some(p) if p < elems.len() => Ok(elems.remove(p)),
+                    _ => Err(Error::IndexOutOfBounds(String::from(path), i))
+                }
+            },
+            _ => Err(not_found())
+        }
+    }
+
+    fn walk_mut(&mut self, path: &str, path_parts: &[PathElement]) -> Result<&mut Value, Error> {
+        let not_found = || Error::NotFound(String::from(path));
+        path_parts.iter().try_fold(self, |v, part| {
+            match (v, part) {
+                (Value::Object(obj), PathElement::Key(key)) => obj.get_mut(key).ok_or_else(not_found),
+                (Value::Object(obj), PathElement::Index(i)) => obj.get_mut(&i.to_string()).ok_or_else(not_found),
+                (Value::Array(elems), PathElement::Key(key)) => {
+                    let i = key.parse().map_err(|_| not_found())?;
+                    element_mut(elems, i).ok_or_else(|| Error::IndexOutOfBounds(String::from(path), i))
                 },
-                _ => Err(Error::IncompatibleType)
+                (Value::Array(elems), &PathElement::Index(i)) => {
+                    element_mut(elems, i).ok_or_else(|| Error::IndexOutOfBounds(String::from(path), i))
+                },
+                _ => Err(not_found())
+            }
+        })
+    }
+
+    /// Merges this value over `fallback`, the way a key set twice in a document
+    /// is merged: objects are merged recursively, keeping the keys of `fallback`
+    /// in their position, and any other value hides the fallback entirely.
+    pub fn with_fallback(&self, fallback: &Value) -> Value {
+        merge(self.clone(), fallback.clone())
+    }
+
+    /// Where the value at `path` was set, if it was read from a document. An
+    /// array element has the origin of the field holding the array. When a key
+    /// is set several times, by repeated fields, includes or merges, this is
+    /// the origin of the value that won.
+    pub fn origin(&self, path: &str) -> Option<&Origin> {
+        let path_parts = parse_lookup_path(path).ok()?;
+        let mut current = self;
+        let mut origin = None;
+        for part in &path_parts {
+            current = match (current, part) {
+                (Value::Object(obj), PathElement::Key(key)) => {
+                    origin = obj.origin(key);
+                    obj.get(key)?
+                },
+                (Value::Object(obj), PathElement::Index(i)) => {
+                    let key = i.to_string();
+                    origin = obj.origin(&key);
+                    obj.get(&key)?
+                },
+                (Value::Array(elems), PathElement::Key(key)) => element(elems, key.parse().ok()?)?,
+                (Value::Array(elems), &PathElement::Index(i)) => element(elems, i)?,
+                _ => return None
+            };
+        }
+        origin
+    }
+
+    /// Converts the value at `path`, adding where the value was set to conversion errors.
+    pub(crate) fn get_as<'a, T, F>(&'a self, path: &str, convert: F) -> Result<T, Error>
+        where F: FnOnce(&'a Value) -> Result<T, Error>
+    {
+        convert(self.get_ref(path)?).map_err(|err| {
+            match self.origin(path) {
+                Some(origin) => Error::AtOrigin(Box::new(err), origin.clone()),
+                None => err
             }
         })
     }
 
+    fn walk(&self, path: &str, path_parts: &[PathElement]) -> Result<&Value, Error> {
+        let not_found = || Error::NotFound(String::from(path));
+        path_parts.iter().try_fold(self, |v, part| {
+            match (v, part) {
+                (Value::Object(obj), PathElement::Key(key)) => obj.get(key).ok_or_else(not_found),
+                (Value::Object(obj), PathElement::Index(i)) => obj.get(&i.to_string()).ok_or_else(not_found),
+                (Value::Array(elems), PathElement::Key(key)) => {
+                    let i = key.parse().map_err(|_| not_found())?;
+                    element(elems, i).ok_or_else(|| Error::IndexOutOfBounds(String::from(path), i))
+                },
+                (Value::Array(elems), &PathElement::Index(i)) => {
+                    element(elems, i).ok_or_else(|| Error::IndexOutOfBounds(String::from(path), i))
+                },
+                _ => Err(not_found())
+            }
+        })
+    }
+
+    pub fn get_bool(&self, path: &str) -> Result<bool, Error> {
+        self.get_as(path, bool_value)
+    }
+
     pub fn get_bool_or(&self, path: &str, default: bool) -> bool {
         self.get_bool(path).unwrap_or(default)
     }
 
     pub fn get_int(&self, path: &str) -> Result<i64, Error> {
-        self.get(path).and_then(|v| {
-            match v {
-                Value::Int(i) => Ok(i),
-                _ => Err(Error::IncompatibleType)
-            }
-        })
+        self.get_as(path, int_value)
     }
 
     pub fn get_int_or(&self, path: &str, default: i64) -> i64 {
@@ -72,13 +318,7 @@ impl Value {
     }
 
     pub fn get_float(&self, path: &str) -> Result<f64, Error> {
-        self.get(path).and_then(|v| {
-            match v {
-                Value::Float(f) => Ok(f),
-                Value::Int(i) => Ok(i as f64),
-                _ => Err(Error::IncompatibleType)
-            }
-        })
+        self.get_as(path, float_value)
     }
 
     pub fn get_float_or(&self, path: &str, default: f64) -> f64 {
@@ -86,16 +326,356 @@ impl Value {
     }
 
     pub fn get_string(&self, path: &str) -> Result<String, Error> {
-        self.get(path).and_then(|v| {
-            match v {
-                Value::String(s) => Ok(s),
+        self.get_as(path, string_value)
+    }
+
+    pub fn get_string_or(&self, path: &str, default: &str) -> String {
+        self.get_string(path).unwrap_or(String::from(default))
+    }
+
+    /// Like `get_string`, but borrows the string.
+    pub fn get_str(&self, path: &str) -> Result<&str, Error> {
+        self.get_as(path, |v| {
+            match *v {
+                Value::String(ref s) => Ok(s.as_str()),
                 _ => Err(Error::IncompatibleType)
             }
         })
     }
 
-    pub fn get_string_or(&self, path: &str, default: &str) -> String {
-        self.get_string(path).unwrap_or(String::from(default))
+    /// Reads a duration such as `30s`, `5 minutes` or `1.5h`. Numbers without
+    /// a unit are milliseconds.
+    pub fn get_duration(&self, path: &str) -> Result<Duration, Error> {
+        self.get_as(path, |v| duration_value(path, v))
     }
 
+    pub fn get_duration_or(&self, path: &str, default: Duration) -> Duration {
+        self.get_duration(path).unwrap_or(default)
+    }
+
+    /// Reads a size in bytes such as `512k`, `64 MiB` or `1.5G`. Units like `kB`
+    /// are powers of ten and units like `K` or `KiB` powers of two. Numbers
+    /// without a unit are bytes.
+    pub fn get_bytes(&self, path: &str) -> Result<u64, Error> {
+        self.get_as(path, |v| bytes_value(path, v))
+    }
+
+    pub fn get_bytes_or(&self, path: &str, default: u64) -> u64 {
+        self.get_bytes(path).unwrap_or(default)
+    }
+
+    /// Reads an array. An object whose keys are integers is read as the list of
+    /// its values ordered by key, ignoring other keys, the way HOCON converts them.
+    pub fn get_list(&self, path: &str) -> Result<Vec<Value>, Error> {
+        self.get_as(path, list_elements).map(|elems| elems.into_iter().cloned().collect())
+    }
+
+    pub fn get_bool_list(&self, path: &str) -> Result<Vec<bool>, Error> {
+        self.get_typed_list(path, |_, v| bool_value(v))
+    }
+
+    pub fn get_int_list(&self, path: &str) -> Result<Vec<i64>, Error> {
+        self.get_typed_list(path, |_, v| int_value(v))
+    }
+
+    pub fn get_float_list(&self, path: &str) -> Result<Vec<f64>, Error> {
+        self.get_typed_list(path, |_, v| float_value(v))
+    }
+
+    pub fn get_string_list(&self, path: &str) -> Result<Vec<String>, Error> {
+        self.get_typed_list(path, |_, v| string_value(v))
+    }
+
+    pub fn get_duration_list(&self, path: &str) -> Result<Vec<Duration>, Error> {
+        self.get_typed_list(path, duration_value)
+    }
+
+    pub fn get_bytes_list(&self, path: &str) -> Result<Vec<u64>, Error> {
+        self.get_typed_list(path, bytes_value)
+    }
+
+    /// Reads a list whose elements must all be objects.
+    pub fn get_object_list(&self, path: &str) -> Result<Vec<Value>, Error> {
+        self.get_typed_list(path, |_, v| {
+            match *v {
+                Value::Object(_) => Ok(v.clone()),
+                _ => Err(Error::IncompatibleType)
+            }
+        })
+    }
+
+    /// Converts every element of the list at `path`, given the element's own path.
+    fn get_typed_list<T, F>(&self, path: &str, convert: F) -> Result<Vec<T>, Error>
+        where F: Fn(&str, &Value) -> Result<T, Error>
+    {
+        self.get_as(path, |value| {
+            list_elements(value)?.into_iter().enumerate().map(|(i, v)| {
+                convert(&format!("{}[{}]", path, i), v).map_err(|e| Error::ListElement(String::from(path), i, Box::new(e)))
+            }).collect()
+        })
+    }
+
+}
+
+macro_rules! from_primitive {
+    ($($ty:ty => $variant:ident as $into:ty),*) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(v: $ty) -> Value {
+                    Value::$variant(<$into>::from(v))
+                }
+            }
+        )*
+    }
+}
+
+from_primitive!(
+    bool => Boolean as bool,
+    i8 => Int as i64, i16 => Int as i64, i32 => Int as i64, i64 => Int as i64,
+    u8 => Int as i64, u16 => Int as i64, u32 => Int as i64,
+    f32 => Float as f64, f64 => Float as f64,
+    char => String as String, String => String as String, &str => String as String
+);
+
+macro_rules! try_from_integer {
+    ($($ty:ty),*) => {
+        $(
+            impl TryFrom<$ty> for Value {
+                type Error = TryFromIntError;
+
+                fn try_from(v: $ty) -> Result<Value, TryFromIntError> {
+                    i64::try_from(v).map(Value::Int)
+                }
+            }
+        )*
+    }
+}
+
+// integers that don't always fit in an `i64`
+try_from_integer!(u64, usize, isize, i128, u128);
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(elems: Vec<T>) -> Value {
+        Value::Array(elems.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Keys are sorted, so the object doesn't depend on the order of the hash map.
+impl<T: Into<Value>> From<HashMap<String, T>> for Value {
+    fn from(map: HashMap<String, T>) -> Value {
+        let mut entries: Vec<(String, T)> = map.into_iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        Value::Object(entries.into_iter().map(|(key, value)| (key, value.into())).collect())
+    }
+}
+
+impl From<Map> for Value {
+    fn from(obj: Map) -> Value {
+        Value::Object(obj)
+    }
+}
+
+fn parse_lookup_path(path: &str) -> Result<Vec<PathElement>, Error> {
+    match lookup_path(path.as_bytes()) {
+        IResult::Done(&[], path_parts) => Ok(path_parts),
+        _ => Err(Error::PathError(String::from(path)))
+    }
+}
+
+/// Position of `index` in an array of `len` elements, counting from the end if negative.
+fn position(len: usize, index: i64) -> Option<usize> {
+    let i = if index < 0 { len as i64 + index } else { index };
+    if i < 0 {
+        None
+    } else {
+        Some(i as usize)
+    }
+}
+
+fn element(elems: &[Value], index: i64) -> Option<&Value> {
+    position(elems.len(), index).and_then(|i| elems.get(i))
+}
+
+fn element_mut(elems: &mut [Value], index: i64) -> Option<&mut Value> {
+    position(elems.len(), index).and_then(move |i| elems.get_mut(i))
+}
+
+/// Array index a step of a path stands for, if it can be one.
+fn array_index(part: &PathElement) -> Option<i64> {
+    match *part {
+        PathElement::Key(ref key) => key.parse().ok(),
+        PathElement::Index(i) => Some(i)
+    }
+}
+
+/// Object key a step of a path stands for.
+fn object_key(part: &PathElement) -> String {
+    match *part {
+        PathElement::Key(ref key) => key.clone(),
+        PathElement::Index(i) => i.to_string()
+    }
+}
+
+/// Fields of `value`, replacing it with an empty object first if it isn't one.
+pub(crate) fn object_fields(value: &mut Value) -> &mut Map {
+    if let Value::Object(_) = *value {
+    } else {
+        *value = Value::Object(Map::new());
+    }
+    match *value {
+        Value::Object(ref mut obj) => obj,
+        _ => unreachable!()
+    }
+}
+
+/// Value that `part` leads to inside `value`, for `set` to go through. Missing
+/// keys are added with an empty object.
+fn slot<'a>(value: &'a mut Value, part: &PathElement, path: &str) -> Result<&'a mut Value, Error> {
+    match (value, array_index(part)) {
+        (&mut Value::Array(ref mut elems), Some(i)) => {
+            element_mut(elems, i).ok_or_else(|| Error::IndexOutOfBounds(String::from(path), i))
+        },
+        (value, _) => Ok(object_fields(value).get_or_insert_with(object_key(part), || Value::Object(Map::new())))
+    }
+}
+
+fn bool_value(value: &Value) -> Result<bool, Error> {
+    match *value {
+        Value::Boolean(b) => Ok(b),
+        Value::String(ref s) => {
+            if s == "true" || s == "yes" || s == "on" {
+                Ok(true)
+            } else if s == "false" || s == "no" || s == "off" {
+                Ok(false)
+            } else {
+                Err(Error::IncompatibleType)
+            }
+        },
+        _ => Err(Error::IncompatibleType)
+    }
+}
+
+fn int_value(value: &Value) -> Result<i64, Error> {
+    match *value {
+        Value::Int(i) => Ok(i),
+        _ => Err(Error::IncompatibleType)
+    }
+}
+
+fn float_value(value: &Value) -> Result<f64, Error> {
+    match *value {
+        Value::Float(f) => Ok(f),
+        Value::Int(i) => Ok(i as f64),
+        _ => Err(Error::IncompatibleType)
+    }
+}
+
+fn string_value(value: &Value) -> Result<String, Error> {
+    match *value {
+        Value::String(ref s) => Ok(s.clone()),
+        _ => Err(Error::IncompatibleType)
+    }
+}
+
+/// `path` is only used in errors.
+fn duration_value(path: &str, value: &Value) -> Result<Duration, Error> {
+    let (number, unit) = number_with_unit(value)?;
+    let nanos_per_unit: u64 = match &*unit {
+        "" | "ms" | "milli" | "millis" | "millisecond" | "milliseconds" => 1_000_000,
+        "ns" | "nano" | "nanos" | "nanosecond" | "nanoseconds" => 1,
+        "us" | "micro" | "micros" | "microsecond" | "microseconds" => 1_000,
+        "s" | "second" | "seconds" => 1_000_000_000,
+        "m" | "minute" | "minutes" => 60 * 1_000_000_000,
+        "h" | "hour" | "hours" => 60 * 60 * 1_000_000_000,
+        "d" | "day" | "days" => 24 * 60 * 60 * 1_000_000_000,
+        _ => return Err(Error::UnknownUnit(String::from(path), unit))
+    };
+    let out_of_range = || Error::OutOfRange(String::from(path));
+    match number.parse::<u64>() {
+        Ok(n) => {
+            let nanos = u128::from(n) * u128::from(nanos_per_unit);
+            let secs = u64::try_from(nanos / 1_000_000_000).map_err(|_| out_of_range())?;
+            Ok(Duration::new(secs, (nanos % 1_000_000_000) as u32))
+        },
+        Err(_) => {
+            let n: f64 = number.parse().map_err(|_| Error::IncompatibleType)?;
+            Duration::try_from_secs_f64(n * nanos_per_unit as f64 / 1e9).map_err(|_| out_of_range())
+        }
+    }
+}
+
+/// `path` is only used in errors.
+fn bytes_value(path: &str, value: &Value) -> Result<u64, Error> {
+    let (number, unit) = number_with_unit(value)?;
+    let bytes_per_unit: u128 = match &*unit {
+        "" | "B" | "b" | "byte" | "bytes" => 1,
+        "kB" | "kilobyte" | "kilobytes" => 1000,
+        "MB" | "megabyte" | "megabytes" => 1000u128.pow(2),
+        "GB" | "gigabyte" | "gigabytes" => 1000u128.pow(3),
+        "TB" | "terabyte" | "terabytes" => 1000u128.pow(4),
+        "PB" | "petabyte" | "petabytes" => 1000u128.pow(5),
+        "EB" | "exabyte" | "exabytes" => 1000u128.pow(6),
+        "ZB" | "zettabyte" | "zettabytes" => 1000u128.pow(7),
+        "YB" | "yottabyte" | "yottabytes" => 1000u128.pow(8),
+        "K" | "k" | "Ki" | "KiB" | "kibibyte" | "kibibytes" => 1 << 10,
+        "M" | "m" | "Mi" | "MiB" | "mebibyte" | "mebibytes" => 1 << 20,
+        "G" | "g" | "Gi" | "GiB" | "gibibyte" | "gibibytes" => 1 << 30,
+        "T" | "t" | "Ti" | "TiB" | "tebibyte" | "tebibytes" => 1 << 40,
+        "P" | "p" | "Pi" | "PiB" | "pebibyte" | "pebibytes" => 1 << 50,
+        "E" | "e" | "Ei" | "EiB" | "exbibyte" | "exbibytes" => 1 << 60,
+        "Z" | "z" | "Zi" | "ZiB" | "zebibyte" | "zebibytes" => 1 << 70,
+        "Y" | "y" | "Yi" | "YiB" | "yobibyte" | "yobibytes" => 1 << 80,
+        _ => return Err(Error::UnknownUnit(String::from(path), unit))
+    };
+    let out_of_range = || Error::OutOfRange(String::from(path));
+    match number.parse::<u64>() {
+        Ok(n) => u128::from(n).checked_mul(bytes_per_unit)
+            .and_then(|bytes| u64::try_from(bytes).ok())
+            .ok_or_else(out_of_range),
+        Err(_) => {
+            let n: f64 = number.parse().map_err(|_| Error::IncompatibleType)?;
+            let bytes = n * bytes_per_unit as f64;
+            if bytes >= 0.0 && bytes < u64::MAX as f64 {
+                Ok(bytes as u64)
+            } else {
+                Err(out_of_range())
+            }
+        }
+    }
+}
+
+fn list_elements(value: &Value) -> Result<Vec<&Value>, Error> {
+    match *value {
+        Value::Array(ref elems) => Ok(elems.iter().collect()),
+        Value::Object(ref obj) => {
+            let mut indexed: Vec<(u32, &Value)> = obj.iter()
+                // only keys written the way the index is, so `01` or `+1` don't collide with `1`
+                .filter_map(|(key, value)| key.parse::<u32>().ok().filter(|i| i.to_string() == *key).map(|i| (i, value)))
+                .collect();
+            if indexed.is_empty() {
+                return Err(Error::IncompatibleType);
+            }
+            indexed.sort_by_key(|&(i, _)| i);
+            Ok(indexed.into_iter().map(|(_, value)| value).collect())
+        },
+        _ => Err(Error::IncompatibleType)
+    }
+}
+
+/// Splits a value like `30s` or `1.5 GiB` into its number and unit. Plain
+/// numbers have an empty unit.
+fn number_with_unit(value: &Value) -> Result<(String, String), Error> {
+    match *value {
+        Value::Int(i) => Ok((i.to_string(), String::new())),
+        Value::Float(f) => Ok((f.to_string(), String::new())),
+        Value::String(ref s) => {
+            let s = s.trim();
+            let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+')).unwrap_or(s.len());
+            if split == 0 {
+                return Err(Error::IncompatibleType);
+            }
+            Ok((s[..split].to_string(), s[split..].trim_start().to_string()))
+        },
+        _ => Err(Error::IncompatibleType)
+    }
 }